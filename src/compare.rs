@@ -0,0 +1,58 @@
+//! Alignment of the pass pipelines of one function from two different dumps,
+//! e.g. before and after a compiler change, for `--compare`.
+
+use crate::optpipeline::Pass;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// A step of two aligned pipelines, with each pass's position in its full pipeline.
+pub enum AlignedPass<'a> {
+    Both {
+        old: (usize, &'a Pass),
+        new: (usize, &'a Pass),
+    },
+    OldOnly((usize, &'a Pass)),
+    NewOnly((usize, &'a Pass)),
+}
+
+/// Align `old` and `new` by pass name, keeping passes in pipeline order. Passes
+/// that were added or removed between the two pipelines show up on one side only.
+pub fn align_pipelines<'a>(
+    old: &[(usize, &'a Pass)],
+    new: &[(usize, &'a Pass)],
+) -> Vec<AlignedPass<'a>> {
+    let old_names: Vec<&str> = old.iter().map(|(_, pass)| pass.name.as_str()).collect();
+    let new_names: Vec<&str> = new.iter().map(|(_, pass)| pass.name.as_str()).collect();
+
+    let mut aligned = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_names, &new_names) {
+        match op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => {
+                for i in 0..len {
+                    aligned.push(AlignedPass::Both {
+                        old: old[old_index + i],
+                        new: new[new_index + i],
+                    });
+                }
+            }
+            _ => {
+                aligned.extend(
+                    old[op.old_range()]
+                        .iter()
+                        .copied()
+                        .map(AlignedPass::OldOnly),
+                );
+                aligned.extend(
+                    new[op.new_range()]
+                        .iter()
+                        .copied()
+                        .map(AlignedPass::NewOnly),
+                );
+            }
+        }
+    }
+    aligned
+}
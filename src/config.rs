@@ -0,0 +1,148 @@
+//! User-defined IR filter rules loaded from `.optdiff.toml`.
+//!
+//! ```toml
+//! [rules.srcloc]
+//! kind = "inline"            # remove every match, "line" removes every line with a match
+//! pattern = ',? !srcloc !\d+'
+//! default = true             # apply even when not requested with `--rule`
+//! ```
+
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use toml::Spanned;
+
+pub const CONFIG_FILE_NAME: &str = ".optdiff.toml";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    Line,
+    Inline,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub kind: RuleKind,
+    pattern: Spanned<String>,
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl Rule {
+    pub fn pattern(&self) -> &str {
+        self.pattern.get_ref()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub rules: BTreeMap<String, Rule>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to parse config file {}:\n{source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("{}:{line}:{column}: invalid pattern for rule '{rule}':\n{source}", path.display())]
+    InvalidPattern {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        rule: String,
+        source: regex::Error,
+    },
+    #[error("Unknown filter rule '{name}', available rules: {available}")]
+    UnknownRule { name: String, available: String },
+}
+
+impl Config {
+    /// Load the config at `path`, or search the current directory and its ancestors
+    /// for `.optdiff.toml`. A missing config is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match find_config() {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        let config: Config = toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+
+        for (name, rule) in &config.rules {
+            if let Err(source) = Regex::new(rule.pattern()) {
+                let (line, column) = line_column(source_prefix(&text, rule));
+                return Err(ConfigError::InvalidPattern {
+                    path,
+                    line,
+                    column,
+                    rule: name.clone(),
+                    source,
+                });
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Rules that are on by default plus the ones requested by name.
+    pub fn enabled_rules<'a>(&'a self, requested: &[String]) -> Result<Vec<&'a Rule>, ConfigError> {
+        for name in requested {
+            if !self.rules.contains_key(name) {
+                return Err(ConfigError::UnknownRule {
+                    name: name.clone(),
+                    available: if self.rules.is_empty() {
+                        "none".to_string()
+                    } else {
+                        self.rules.keys().cloned().collect::<Vec<_>>().join(", ")
+                    },
+                });
+            }
+        }
+        Ok(self
+            .rules
+            .iter()
+            .filter(|(name, rule)| rule.default || requested.contains(name))
+            .map(|(_, rule)| rule)
+            .collect())
+    }
+}
+
+fn find_config() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The config text up to where the rule's pattern starts.
+fn source_prefix<'a>(source: &'a str, rule: &Rule) -> &'a str {
+    &source[..rule.pattern.span().start.min(source.len())]
+}
+
+/// 1-based line and column of the end of `prefix`.
+fn line_column(prefix: &str) -> (usize, usize) {
+    let line = prefix.matches('\n').count() + 1;
+    let column = prefix.len() - prefix.rfind('\n').map_or(0, |idx| idx + 1) + 1;
+    (line, column)
+}
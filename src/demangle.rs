@@ -1,84 +1,212 @@
-//! Taken from https://github.com/gimli-rs/cpp_demangle/blob/master/examples/cppfilt.rs
+//! Based on https://github.com/gimli-rs/cpp_demangle/blob/master/examples/cppfilt.rs,
+//! extended to the other mangling schemes found in LLVM IR through a registry of
+//! demanglers.
 #![allow(unused)]
 
-use cpp_demangle::{BorrowedSymbol, DemangleOptions};
+mod dlang;
+mod msvc;
+mod swift;
+
+use cpp_demangle::BorrowedSymbol;
+use std::collections::HashMap;
 use std::io::{self, BufRead, Write};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
 
-/// Find the index of the first (potential) occurrence of a mangled C++ symbol
-/// in the given `haystack`.
-fn find_mangled(haystack: &[u8]) -> Option<usize> {
-    if haystack.is_empty() {
-        return None;
-    }
-
-    for i in 0..haystack.len() - 1 {
-        if haystack[i] == b'_' {
-            match (
-                haystack[i + 1],
-                haystack.get(i + 2),
-                haystack.get(i + 3),
-                haystack.get(i + 4),
-            ) {
-                (b'Z', _, _, _) | (b'_', Some(b'Z'), _, _) | (b'_', Some(b'_'), Some(b'Z'), _) => {
-                    return Some(i)
-                }
-                (b'_', Some(b'_'), Some(b'_'), Some(b'Z')) => return Some(i),
-                _ => (),
-            }
+/// A mangling scheme `demangle_line` recognizes.
+trait Demangler: Sync {
+    /// The length of the scheme's prefix if a symbol of it may start at `text`,
+    /// which follows `prev` in the line.
+    fn prefix_len(&self, prev: Option<u8>, text: &[u8]) -> Option<usize>;
+
+    /// Demangle the symbol at the start of `text` and return it along with the
+    /// rest of `text`.
+    fn demangle<'a>(&self, text: &'a [u8], options: &DemangleOptions)
+        -> Option<(String, &'a [u8])>;
+}
+
+/// All known demanglers, in the order they get to claim a symbol.
+static DEMANGLERS: &[&dyn Demangler] = &[&Rust, &Itanium, &msvc::Msvc, &swift::Swift, &dlang::D];
+
+/// Find the index of the first (potential) occurrence of a mangled symbol in the
+/// given `haystack`, starting at `start`.
+fn find_mangled(haystack: &[u8], start: usize) -> Option<usize> {
+    // Every scheme starts with one of these
+    memchr::memchr3_iter(b'_', b'?', b'$', &haystack[start..])
+        .map(|idx| start + idx)
+        .find(|&idx| {
+            let prev = idx.checked_sub(1).map(|prev| haystack[prev]);
+            DEMANGLERS
+                .iter()
+                .any(|demangler| demangler.prefix_len(prev, &haystack[idx..]).is_some())
+        })
+}
+
+/// Whether `c` can be part of an unquoted LLVM identifier.
+fn is_symbol_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'$' | b'.')
+}
+
+/// Whether a symbol starting after `prev` starts an identifier, for schemes with
+/// prefixes that are too common in the middle of one.
+fn at_identifier_start(prev: Option<u8>) -> bool {
+    !prev.is_some_and(is_symbol_char)
+}
+
+/// The length of the identifier at the start of `text`, made of the bytes
+/// `is_part` accepts.
+fn token_len(text: &[u8], is_part: impl Fn(u8) -> bool) -> usize {
+    text.iter().position(|&c| !is_part(c)).unwrap_or(text.len())
+}
+
+/// Itanium C++ symbols (`_Z`, with up to four leading underscores).
+struct Itanium;
+
+impl Demangler for Itanium {
+    fn prefix_len(&self, _prev: Option<u8>, text: &[u8]) -> Option<usize> {
+        let underscores = text.iter().take(4).take_while(|&&c| c == b'_').count();
+        (underscores > 0 && text.get(underscores) == Some(&b'Z')).then_some(underscores + 1)
+    }
+
+    fn demangle<'a>(
+        &self,
+        text: &'a [u8],
+        options: &DemangleOptions,
+    ) -> Option<(String, &'a [u8])> {
+        let (sym, tail) = BorrowedSymbol::with_tail(text).ok()?;
+        Some((sym.demangle(&options.itanium()).ok()?, tail))
+    }
+}
+
+/// Rust symbols, in the v0 (`_R`) and the legacy scheme.
+///
+/// Legacy Rust symbols are valid Itanium symbols, but `cpp_demangle` can't decode
+/// their `$LT$`-style escapes and keeps the hash, so they are told apart by the
+/// `17h<hash>E` they end in.
+struct Rust;
+
+impl Demangler for Rust {
+    fn prefix_len(&self, prev: Option<u8>, text: &[u8]) -> Option<usize> {
+        match text {
+            [b'_', b'Z', b'N', ..] => Some(3),
+            [b'_', b'_', b'Z', b'N', ..] => Some(4),
+            [b'_', b'R', ..] if at_identifier_start(prev) => Some(2),
+            [b'_', b'_', b'R', ..] if at_identifier_start(prev) => Some(3),
+            _ => None,
         }
     }
 
-    None
+    fn demangle<'a>(
+        &self,
+        text: &'a [u8],
+        _options: &DemangleOptions,
+    ) -> Option<(String, &'a [u8])> {
+        let len = token_len(text, is_symbol_char);
+        let symbol = std::str::from_utf8(&text[..len]).ok()?;
+        let v0 = symbol.starts_with("_R") || symbol.starts_with("__R");
+        if !v0 && !is_rust_legacy(symbol) {
+            return None;
+        }
+        let demangled = rustc_demangle::try_demangle(symbol).ok()?;
+        Some((format!("{:#}", demangled), &text[len..]))
+    }
+}
+
+/// Whether `symbol` is an Itanium-style `_ZN...E` name with a Rust hash as its
+/// last path component.
+fn is_rust_legacy(symbol: &str) -> bool {
+    let symbol = symbol.trim_start_matches('_');
+    symbol.starts_with("ZN")
+        && symbol.match_indices("17h").any(|(idx, _)| {
+            let hash = &symbol.as_bytes()[idx + 3..];
+            hash.len() > 16 && hash[..16].iter().all(u8::is_ascii_hexdigit) && hash[16] == b'E'
+        })
 }
 
-/// Print the given `line` to `out`, with all mangled C++ symbols replaced with
-/// their demangled form.
+/// Print the given `line` to `out`, with all mangled symbols replaced with their
+/// demangled form.
 pub fn demangle_line<W>(out: &mut W, line: &[u8], options: DemangleOptions) -> io::Result<()>
 where
     W: Write,
 {
-    let mut line = line;
-
-    while let Some(idx) = find_mangled(line) {
-        write!(out, "{}", String::from_utf8_lossy(&line[..idx]))?;
-
-        let prefix_len = if idx + 1 < line.len() {
-            match (
-                line[idx + 1],
-                line.get(idx + 2),
-                line.get(idx + 3),
-                line.get(idx + 4),
-            ) {
-                (b'Z', _, _, _) => 2,                            // _Z
-                (b'_', Some(b'Z'), _, _) => 3,                   // __Z
-                (b'_', Some(b'_'), Some(b'Z'), _) => 4,          // ___Z
-                (b'_', Some(b'_'), Some(b'_'), Some(b'Z')) => 5, // ____Z
-                _ => 2, // fallback case, shouldn't happen due to find_mangled logic
+    let mut pos = 0;
+
+    while let Some(idx) = find_mangled(line, pos) {
+        write!(out, "{}", String::from_utf8_lossy(&line[pos..idx]))?;
+
+        let prev = idx.checked_sub(1).map(|prev| line[prev]);
+        match demangle_candidate(prev, &line[idx..], options) {
+            Candidate::Symbol(demangled, len) => {
+                write!(out, "{}", demangled)?;
+                pos = idx + len;
+            }
+            Candidate::Prefix(len) => {
+                write!(out, "{}", String::from_utf8_lossy(&line[idx..idx + len]))?;
+                pos = idx + len;
             }
-        } else {
-            2 // fallback case for end of input
-        };
-
-        if let Ok((sym, tail)) = BorrowedSymbol::with_tail(&line[idx..]) {
-            let demangled = sym
-                .demangle(&options)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            write!(out, "{}", demangled)?;
-            line = tail;
-        } else {
-            write!(
-                out,
-                "{}",
-                String::from_utf8_lossy(&line[idx..idx + prefix_len])
-            )?;
-            line = &line[idx + prefix_len..];
         }
     }
 
-    write!(out, "{}", String::from_utf8_lossy(line))
+    write!(out, "{}", String::from_utf8_lossy(&line[pos..]))
 }
 
-/// Print all the lines from the given `input` to `out`, with all mangled C++
+/// What a potential symbol found by `find_mangled` turned out to be.
+#[derive(Clone)]
+enum Candidate {
+    /// A symbol, demangled, and the length of its mangled form
+    Symbol(Arc<str>, usize),
+    /// No symbol after all, to be skipped up to the end of the longest prefix
+    /// of a scheme it starts with
+    Prefix(usize),
+}
+
+/// Candidates seen so far, by options, by whether they start an identifier and
+/// by the run of `is_mangled_char` bytes they start.
+type SymbolCache = HashMap<(DemangleOptions, bool), HashMap<Box<[u8]>, Candidate>>;
+
+/// Shared by all threads, the same symbols show up in every pass.
+static SYMBOLS: LazyLock<RwLock<SymbolCache>> = LazyLock::new(Default::default);
+
+/// Whether `c` can be part of a symbol in any scheme. A symbol never extends
+/// past them, so a run of them decides what it demangles to.
+fn is_mangled_char(c: u8) -> bool {
+    is_symbol_char(c) || matches!(c, b'?' | b'@')
+}
+
+/// Demangle the potential symbol at the start of `text`, which follows `prev`
+/// in the line.
+fn demangle_candidate(prev: Option<u8>, text: &[u8], options: DemangleOptions) -> Candidate {
+    let token = &text[..token_len(text, is_mangled_char)];
+    let key = (options, at_identifier_start(prev));
+    if let Some(candidate) = SYMBOLS
+        .read()
+        .unwrap()
+        .get(&key)
+        .and_then(|symbols| symbols.get(token))
+    {
+        return candidate.clone();
+    }
+
+    // Skip the longest prefix if no demangler takes the symbol
+    let mut prefix_len = 1;
+    let demangled = DEMANGLERS.iter().find_map(|demangler| {
+        prefix_len = prefix_len.max(demangler.prefix_len(prev, token)?);
+        demangler.demangle(token, &options)
+    });
+    let candidate = match demangled {
+        Some((demangled, tail)) => Candidate::Symbol(demangled.into(), token.len() - tail.len()),
+        None => Candidate::Prefix(prefix_len),
+    };
+
+    SYMBOLS
+        .write()
+        .unwrap()
+        .entry(key)
+        .or_default()
+        .insert(token.into(), candidate.clone());
+    candidate
+}
+
+/// Print all the lines from the given `input` to `out`, with all mangled
 /// symbols replaced with their demangled form.
 pub fn demangle_all<R, W>(input: &mut R, out: &mut W, options: DemangleOptions) -> io::Result<()>
 where
@@ -102,35 +230,142 @@ where
     Ok(())
 }
 
-pub struct DemangleBuilder {
-    options: DemangleOptions,
+/// `text` with all mangled symbols demangled as `mode` says, or unchanged if
+/// `mode` is `None` or demangling fails.
+pub fn demangle_text(text: &str, mode: Option<DemangleMode>) -> String {
+    let Some(mode) = mode else {
+        return text.to_string();
+    };
+
+    let mut output = Vec::new();
+    if demangle_line(&mut output, text.as_bytes(), mode.options()).is_ok() {
+        String::from_utf8_lossy(&output).to_string()
+    } else {
+        text.to_string()
+    }
 }
 
-impl Default for DemangleBuilder {
-    fn default() -> Self {
-        Self {
-            options: DemangleOptions::new(),
+/// IR bodies demangled by `demangle_text`, by their text. Neighbouring passes
+/// share bodies, the IR after one pass is the IR before the next, so each is
+/// only demangled once.
+pub struct BodyCache {
+    mode: Option<DemangleMode>,
+    bodies: Mutex<Bodies>,
+}
+
+#[derive(Default)]
+struct Bodies {
+    demangled: HashMap<Arc<str>, Arc<str>>,
+    /// Bytes held, of the bodies and their demangled form
+    size: usize,
+}
+
+/// Bytes a `BodyCache` holds before it starts over, so that it doesn't keep a
+/// whole dump in memory.
+const MAX_BODY_CACHE_SIZE: usize = 64 << 20;
+
+impl BodyCache {
+    pub fn new(mode: Option<DemangleMode>) -> Self {
+        BodyCache {
+            mode,
+            bodies: Mutex::default(),
+        }
+    }
+
+    /// `body` demangled as `demangle_text` does.
+    pub fn demangle(&self, body: &str) -> Arc<str> {
+        if self.mode.is_none() {
+            return Arc::from(body);
+        }
+        if let Some(demangled) = self.bodies.lock().unwrap().demangled.get(body) {
+            return demangled.clone();
+        }
+
+        // Demangle without holding the lock, other threads have bodies of their own
+        let demangled: Arc<str> = Arc::from(demangle_text(body, self.mode));
+        let mut bodies = self.bodies.lock().unwrap();
+        if bodies.size > MAX_BODY_CACHE_SIZE {
+            *bodies = Bodies::default();
         }
+        bodies.size += body.len() + demangled.len();
+        bodies.demangled.insert(Arc::from(body), demangled.clone());
+        demangled
     }
 }
 
+/// How much of a symbol `--demangle` shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DemangleMode {
+    /// The whole signature
+    Full,
+    /// Leave out function parameters
+    NoParams,
+    /// Leave out function parameters, return types and the types of literals in
+    /// template arguments
+    Short,
+}
+
+impl DemangleMode {
+    pub fn options(self) -> DemangleOptions {
+        let builder = DemangleBuilder::new();
+        match self {
+            DemangleMode::Full => builder,
+            DemangleMode::NoParams => builder.no_params(),
+            DemangleMode::Short => builder
+                .no_params()
+                .no_return_type()
+                .hide_expression_literal_types(),
+        }
+        .build()
+    }
+}
+
+/// What the demanglers leave out of a symbol.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DemangleOptions {
+    no_params: bool,
+    no_return_type: bool,
+    hide_expression_literal_types: bool,
+}
+
+impl DemangleOptions {
+    fn itanium(self) -> cpp_demangle::DemangleOptions {
+        let mut options = cpp_demangle::DemangleOptions::new();
+        if self.no_params {
+            options = options.no_params();
+        }
+        if self.no_return_type {
+            options = options.no_return_type();
+        }
+        if self.hide_expression_literal_types {
+            options = options.hide_expression_literal_types();
+        }
+        options
+    }
+}
+
+#[derive(Default)]
+pub struct DemangleBuilder {
+    options: DemangleOptions,
+}
+
 impl DemangleBuilder {
     pub fn new() -> Self {
         Self::default()
     }
 
     pub fn no_params(mut self) -> Self {
-        self.options = self.options.no_params();
+        self.options.no_params = true;
         self
     }
 
     pub fn no_return_type(mut self) -> Self {
-        self.options = self.options.no_return_type();
+        self.options.no_return_type = true;
         self
     }
 
     pub fn hide_expression_literal_types(mut self) -> Self {
-        self.options = self.options.hide_expression_literal_types();
+        self.options.hide_expression_literal_types = true;
         self
     }
 
@@ -138,3 +373,94 @@ impl DemangleBuilder {
         self.options
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demangled(text: &str, mode: DemangleMode) -> String {
+        demangle_text(text, Some(mode))
+    }
+
+    #[test]
+    fn rust_legacy_symbols() {
+        let call = "call void @_ZN4core3ptr13drop_in_place17h0123456789abcdefE(ptr %x)";
+        for mode in [
+            DemangleMode::Full,
+            DemangleMode::NoParams,
+            DemangleMode::Short,
+        ] {
+            assert_eq!(
+                demangled(call, mode),
+                "call void @core::ptr::drop_in_place(ptr %x)"
+            );
+        }
+        // LLVM quotes names with `$`, and macOS adds an underscore
+        assert_eq!(
+            demangled(
+                "@\"_ZN5alloc3vec16Vec$LT$T$C$A$GT$4push17h9e5c1d0b7a2f3e41E\"",
+                DemangleMode::Full
+            ),
+            "@\"alloc::vec::Vec<T,A>::push\""
+        );
+        assert_eq!(
+            demangled(
+                "@__ZN4core3ptr13drop_in_place17h0123456789abcdefE",
+                DemangleMode::Full
+            ),
+            "@core::ptr::drop_in_place"
+        );
+    }
+
+    #[test]
+    fn rust_v0_symbols() {
+        assert_eq!(
+            demangled("@_RNvNtCs1234_7mycrate3bar3foo(", DemangleMode::Full),
+            "@mycrate::bar::foo("
+        );
+        assert_eq!(
+            demangled("@_RINvNtC3std3mem8align_ofjEC3foo", DemangleMode::Short),
+            "@std::mem::align_of::<usize>"
+        );
+        assert_eq!(
+            demangled(
+                "@_RNvMs_NtCs4fqI2P2rA04_4core3fmtNtB4_9Arguments3new",
+                DemangleMode::Full
+            ),
+            "@<core::fmt::Arguments>::new"
+        );
+    }
+
+    #[test]
+    fn itanium_symbols() {
+        let name = "@_ZN3foo3barIiEEvT_";
+        assert_eq!(
+            demangled(name, DemangleMode::Full),
+            "@void foo::bar<int>(int)"
+        );
+        assert_eq!(
+            demangled(name, DemangleMode::NoParams),
+            "@void foo::bar<int>"
+        );
+        assert_eq!(demangled(name, DemangleMode::Short), "@foo::bar<int>");
+        assert_eq!(
+            demangled("@_ZN3foo3barEv", DemangleMode::Full),
+            "@foo::bar()"
+        );
+        assert_eq!(
+            demangled("@_ZN3foo3barEv", DemangleMode::NoParams),
+            "@foo::bar"
+        );
+    }
+
+    #[test]
+    fn leaves_other_names_alone() {
+        for text in [
+            "%foo_Rxyz = add i32 %a, %b",
+            // one hex digit short of a Rust hash, and not valid Itanium either
+            "@_ZN4core3ptr13drop_in_place17h0123456789abcdeE",
+        ] {
+            assert_eq!(demangled(text, DemangleMode::Full), text);
+        }
+    }
+}
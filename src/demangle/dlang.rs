@@ -0,0 +1,337 @@
+//! D symbols (`_D4test3fooFiZi`), printed like `core.demangle` does.
+//!
+//! Covers functions and variables with basic, array, pointer and aggregate
+//! types, including back references. Symbols using anything else (templates,
+//! nested functions, delegates, ...) are left mangled.
+
+use super::{at_identifier_start, token_len, DemangleOptions, Demangler};
+
+pub struct D;
+
+impl Demangler for D {
+    fn prefix_len(&self, prev: Option<u8>, text: &[u8]) -> Option<usize> {
+        match text {
+            [b'_', b'D', b'1'..=b'9', ..] if at_identifier_start(prev) => Some(2),
+            _ => None,
+        }
+    }
+
+    fn demangle<'a>(
+        &self,
+        text: &'a [u8],
+        options: &DemangleOptions,
+    ) -> Option<(String, &'a [u8])> {
+        let len = token_len(text, |c| c.is_ascii_alphanumeric() || c == b'_');
+        let mut parser = Parser {
+            text: &text[..len],
+            options: *options,
+            pos: 2,
+        };
+        let demangled = parser.symbol()?;
+        Some((demangled, &text[len..]))
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    options: DemangleOptions,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, prefix: &[u8]) -> bool {
+        let found = self.text[self.pos..].starts_with(prefix);
+        if found {
+            self.pos += prefix.len();
+        }
+        found
+    }
+
+    fn symbol(&mut self) -> Option<String> {
+        let name = self.qualified_name()?;
+        // `__init` and other compiler generated symbols have no type
+        if self.eat(b"Z") || self.peek().is_none() {
+            return self.peek().is_none().then_some(name);
+        }
+        let demangled = if self.eat(b"M") {
+            let this = self.type_modifier();
+            self.function(&name, this)?
+        } else if self.at_function() {
+            self.function(&name, "")?
+        } else {
+            let ty = self.ty()?;
+            if self.options.no_return_type {
+                name
+            } else {
+                format!("{} {}", ty, name)
+            }
+        };
+        self.peek().is_none().then_some(demangled)
+    }
+
+    fn qualified_name(&mut self) -> Option<String> {
+        let mut names = Vec::new();
+        while let Some(c) = self.peek() {
+            match c {
+                b'0'..=b'9' => names.push(self.identifier()?),
+                b'Q' => {
+                    self.pos += 1;
+                    let position = self.back_reference()?;
+                    let pos = std::mem::replace(&mut self.pos, position);
+                    let name = self.identifier();
+                    self.pos = pos;
+                    names.push(name?);
+                }
+                _ => break,
+            }
+        }
+        (!names.is_empty()).then(|| names.join("."))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let len = token_len(&self.text[self.pos..], |c| c.is_ascii_digit());
+        let number = std::str::from_utf8(&self.text[self.pos..self.pos.checked_add(len)?])
+            .ok()?
+            .parse()
+            .ok()?;
+        self.pos += len;
+        Some(number)
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        let len = self.number()?;
+        // The length comes from the symbol, so it may be anything
+        let end = self.pos.checked_add(len)?;
+        let identifier = std::str::from_utf8(self.text.get(self.pos..end)?).ok()?;
+        // Template instances
+        if identifier.starts_with("__T") || identifier.starts_with("__U") {
+            return None;
+        }
+        self.pos += len;
+        Some(identifier.to_string())
+    }
+
+    /// The position a back reference after a `Q` refers to, as a distance
+    /// backwards from the `Q` in base 26, with uppercase digits and a lowercase
+    /// last one.
+    fn back_reference(&mut self) -> Option<usize> {
+        let start = self.pos - 1;
+        let mut distance = 0usize;
+        loop {
+            let c = self.next()?;
+            match c {
+                b'A'..=b'Z' => {
+                    distance = distance.checked_mul(26)?.checked_add((c - b'A') as usize)?
+                }
+                b'a'..=b'z' => {
+                    distance = distance.checked_mul(26)?.checked_add((c - b'a') as usize)?;
+                    break;
+                }
+                _ => return None,
+            }
+        }
+        start.checked_sub(distance).filter(|_| distance > 0)
+    }
+
+    fn at_function(&self) -> bool {
+        matches!(self.peek(), Some(b'F' | b'U' | b'W' | b'V' | b'R'))
+    }
+
+    /// The qualifier of a type or of the `this` of a member function.
+    fn type_modifier(&mut self) -> &'static str {
+        if self.eat(b"x") {
+            " const"
+        } else if self.eat(b"y") {
+            " immutable"
+        } else if self.eat(b"O") {
+            " shared"
+        } else if self.eat(b"Ng") {
+            " inout"
+        } else {
+            ""
+        }
+    }
+
+    /// A function, with `this` as the qualifier of member functions.
+    fn function(&mut self, name: &str, this: &str) -> Option<String> {
+        let linkage = match self.next()? {
+            b'F' => "",
+            b'U' => "extern (C) ",
+            b'W' => "extern (Windows) ",
+            b'V' => "extern (Pascal) ",
+            b'R' => "extern (C++) ",
+            _ => return None,
+        };
+
+        let mut attributes = String::new();
+        while self.peek() == Some(b'N') {
+            let attribute = match self.text.get(self.pos + 1)? {
+                b'a' => "pure ",
+                b'b' => "nothrow ",
+                b'c' => "ref ",
+                b'd' => "@property ",
+                b'e' => "@trusted ",
+                b'f' => "@safe ",
+                b'i' => "@nogc ",
+                b'j' => "return ",
+                b'l' => "scope ",
+                b'm' => "@live ",
+                _ => break,
+            };
+            self.pos += 2;
+            attributes += attribute;
+        }
+
+        let mut params = Vec::new();
+        loop {
+            match self.peek()? {
+                b'X' | b'Y' => {
+                    self.pos += 1;
+                    params.push("...".to_string());
+                    break;
+                }
+                b'Z' => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {
+                    let storage = if self.eat(b"J") {
+                        "out "
+                    } else if self.eat(b"K") {
+                        "ref "
+                    } else if self.eat(b"L") {
+                        "lazy "
+                    } else if self.eat(b"M") {
+                        "scope "
+                    } else if self.eat(b"I") {
+                        "in "
+                    } else {
+                        ""
+                    };
+                    params.push(format!("{}{}", storage, self.ty()?));
+                }
+            }
+        }
+
+        let return_type = self.ty()?;
+        let signature = if self.options.no_params {
+            String::new()
+        } else {
+            format!("({}){}", params.join(", "), this)
+        };
+        if self.options.no_return_type {
+            Some(format!("{}{}", name, signature))
+        } else {
+            Some(format!(
+                "{}{}{} {}{}",
+                linkage, attributes, return_type, name, signature
+            ))
+        }
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        let ty = match self.next()? {
+            b'v' => "void",
+            b'g' => "byte",
+            b'h' => "ubyte",
+            b's' => "short",
+            b't' => "ushort",
+            b'i' => "int",
+            b'k' => "uint",
+            b'l' => "long",
+            b'm' => "ulong",
+            b'f' => "float",
+            b'd' => "double",
+            b'e' => "real",
+            b'a' => "char",
+            b'u' => "wchar",
+            b'w' => "dchar",
+            b'b' => "bool",
+            b'n' => "typeof(null)",
+            b'A' => return Some(format!("{}[]", self.ty()?)),
+            b'P' => return Some(format!("{}*", self.ty()?)),
+            b'G' => {
+                let len = self.number()?;
+                return Some(format!("{}[{}]", self.ty()?, len));
+            }
+            b'H' => {
+                let key = self.ty()?;
+                return Some(format!("{}[{}]", self.ty()?, key));
+            }
+            b'x' => return Some(format!("const({})", self.ty()?)),
+            b'y' => return Some(format!("immutable({})", self.ty()?)),
+            b'O' => return Some(format!("shared({})", self.ty()?)),
+            b'N' if self.eat(b"g") => return Some(format!("inout({})", self.ty()?)),
+            b'S' | b'C' | b'E' | b'T' | b'I' => return self.qualified_name(),
+            b'Q' => {
+                let position = self.back_reference()?;
+                let pos = std::mem::replace(&mut self.pos, position);
+                let ty = self.ty();
+                self.pos = pos;
+                return ty;
+            }
+            _ => return None,
+        };
+        Some(ty.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::demangle::{demangle_text, DemangleMode};
+
+    #[test]
+    fn functions_and_variables() {
+        for (symbol, demangled) in [
+            ("_D4test3fooFiZi", "int test.foo(int)"),
+            ("_D4test1xi", "int test.x"),
+            (
+                "_D3std5stdio7writelnFAyaZv",
+                "void std.stdio.writeln(immutable(char)[])",
+            ),
+            ("_D4test3barFPiAaZv", "void test.bar(int*, char[])"),
+            (
+                "_D4test3fooFxAaHiiZv",
+                "void test.foo(const(char[]), int[int])",
+            ),
+        ] {
+            assert_eq!(demangle_text(symbol, Some(DemangleMode::Full)), demangled);
+        }
+        let call = "call i32 @_D4test3fooFiZi(i32 1)";
+        assert_eq!(
+            demangle_text(call, Some(DemangleMode::NoParams)),
+            "call i32 @int test.foo(i32 1)"
+        );
+        assert_eq!(
+            demangle_text(call, Some(DemangleMode::Short)),
+            "call i32 @test.foo(i32 1)"
+        );
+    }
+
+    #[test]
+    fn back_references() {
+        // To the `4test` identifier, and to the `S…5Point` type
+        assert_eq!(
+            demangle_text("_D4test3bazFSQl5PointZQk", Some(DemangleMode::Full)),
+            "test.Point test.baz(test.Point)"
+        );
+    }
+
+    #[test]
+    fn leaves_overlong_identifiers_alone() {
+        assert_eq!(
+            demangle_text("_D18446744073709551615x", Some(DemangleMode::Full)),
+            "_D18446744073709551615x"
+        );
+    }
+}
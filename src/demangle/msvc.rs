@@ -0,0 +1,519 @@
+//! MSVC C++ symbols (`?foo@@YAHH@Z`), printed like `llvm-undname` does.
+//!
+//! Covers functions and variables with the common types and templates. Symbols
+//! using anything else (function pointers, thunks, RTTI, ...) are left mangled.
+
+use super::{at_identifier_start, DemangleOptions, Demangler};
+
+pub struct Msvc;
+
+impl Demangler for Msvc {
+    fn prefix_len(&self, prev: Option<u8>, text: &[u8]) -> Option<usize> {
+        match text {
+            [b'?', c, ..]
+                if at_identifier_start(prev)
+                    && (c.is_ascii_alphanumeric() || matches!(c, b'_' | b'?' | b'$')) =>
+            {
+                Some(1)
+            }
+            _ => None,
+        }
+    }
+
+    fn demangle<'a>(
+        &self,
+        text: &'a [u8],
+        options: &DemangleOptions,
+    ) -> Option<(String, &'a [u8])> {
+        let mut parser = Parser {
+            text,
+            options: *options,
+            pos: 0,
+            names: Vec::new(),
+            params: Vec::new(),
+        };
+        let demangled = parser.symbol()?;
+        Some((demangled, &text[parser.pos..]))
+    }
+}
+
+/// Back references (`0`-`9`) refer to one of the first ten entries.
+const MAX_BACK_REFERENCES: usize = 10;
+
+struct Parser<'a> {
+    text: &'a [u8],
+    options: DemangleOptions,
+    pos: usize,
+    /// Name fragments, for back references in names
+    names: Vec<String>,
+    /// Function parameter types longer than one character, for back references
+    /// in parameter lists
+    params: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, prefix: &[u8]) -> bool {
+        let found = self.text[self.pos..].starts_with(prefix);
+        if found {
+            self.pos += prefix.len();
+        }
+        found
+    }
+
+    fn symbol(&mut self) -> Option<String> {
+        if !self.eat(b"?") {
+            return None;
+        }
+        let special = self.eat(b"?");
+        let name = if special {
+            Some(self.operator_name()?)
+        } else {
+            None
+        };
+        let mut scopes = Vec::new();
+        if name.is_none() {
+            scopes.push(self.name_fragment()?);
+        }
+        scopes.extend(self.scopes()?);
+        scopes.reverse();
+        let name = match name {
+            Some(Operator::Constructor) => scopes.last()?.clone(),
+            Some(Operator::Destructor) => format!("~{}", scopes.last()?),
+            Some(Operator::Named(op)) => format!("operator{}", op),
+            None => scopes.pop()?,
+        };
+        let qualified = scopes
+            .into_iter()
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("::");
+
+        match self.next()? {
+            c @ b'0'..=b'4' => self.variable(c, &qualified),
+            c => self.function(c, &qualified),
+        }
+    }
+
+    /// The name after `??`: an operator, constructor or destructor.
+    fn operator_name(&mut self) -> Option<Operator> {
+        let op = match self.next()? {
+            b'0' => return Some(Operator::Constructor),
+            b'1' => return Some(Operator::Destructor),
+            b'2' => " new",
+            b'3' => " delete",
+            b'4' => "=",
+            b'5' => ">>",
+            b'6' => "<<",
+            b'7' => "!",
+            b'8' => "==",
+            b'9' => "!=",
+            b'A' => "[]",
+            b'C' => "->",
+            b'D' => "*",
+            b'E' => "++",
+            b'F' => "--",
+            b'G' => "-",
+            b'H' => "+",
+            b'I' => "&",
+            b'J' => "->*",
+            b'K' => "/",
+            b'L' => "%",
+            b'M' => "<",
+            b'N' => "<=",
+            b'O' => ">",
+            b'P' => ">=",
+            b'Q' => ",",
+            b'R' => "()",
+            b'S' => "~",
+            b'T' => "^",
+            b'U' => "|",
+            b'V' => "&&",
+            b'W' => "||",
+            b'X' => "*=",
+            b'Y' => "+=",
+            b'Z' => "-=",
+            b'_' => match self.next()? {
+                b'0' => "/=",
+                b'1' => "%=",
+                b'2' => ">>=",
+                b'3' => "<<=",
+                b'4' => "&=",
+                b'5' => "|=",
+                b'6' => "^=",
+                b'U' => " new[]",
+                b'V' => " delete[]",
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(Operator::Named(op))
+    }
+
+    /// The enclosing scopes of a name, innermost first, up to the terminating `@`.
+    fn scopes(&mut self) -> Option<Vec<String>> {
+        let mut scopes = Vec::new();
+        while !self.eat(b"@") {
+            if self.eat(b"?A0x") {
+                // Anonymous namespaces are named after a hash
+                while self.next()? != b'@' {}
+                let name = "`anonymous namespace'".to_string();
+                self.remember_name(&name);
+                scopes.push(name);
+            } else {
+                scopes.push(self.name_fragment()?);
+            }
+        }
+        Some(scopes)
+    }
+
+    /// A plain name, a template instance or a back reference to a name.
+    fn name_fragment(&mut self) -> Option<String> {
+        match self.peek()? {
+            c @ b'0'..=b'9' => {
+                self.pos += 1;
+                self.names.get((c - b'0') as usize).cloned()
+            }
+            b'?' if self.eat(b"?$") => {
+                // Template instances have back references of their own
+                let names = std::mem::take(&mut self.names);
+                let params = std::mem::take(&mut self.params);
+                let instance = self.template_instance();
+                self.names = names;
+                self.params = params;
+                let instance = instance?;
+                self.remember_name(&instance);
+                Some(instance)
+            }
+            _ => {
+                let len = self.text[self.pos..].iter().position(|&c| c == b'@')?;
+                let name =
+                    std::str::from_utf8(&self.text[self.pos..self.pos.checked_add(len)?]).ok()?;
+                if name.is_empty()
+                    || !name
+                        .bytes()
+                        .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'$')
+                {
+                    return None;
+                }
+                let name = name.to_string();
+                self.pos = self.pos.checked_add(len)?.checked_add(1)?;
+                self.remember_name(&name);
+                Some(name)
+            }
+        }
+    }
+
+    fn remember_name(&mut self, name: &str) {
+        if self.names.len() < MAX_BACK_REFERENCES && !self.names.iter().any(|n| n == name) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    fn template_instance(&mut self) -> Option<String> {
+        let name = self.name_fragment()?;
+        let mut args = Vec::new();
+        while !self.eat(b"@") {
+            if self.eat(b"$0") {
+                args.push(self.number()?.to_string());
+            } else if self.eat(b"$$V") || self.eat(b"$$Z") || self.eat(b"$S") {
+                // Empty parameter pack
+            } else {
+                args.push(self.ty()?);
+            }
+        }
+        Some(format!("{}<{}>", name, args.join(", ")))
+    }
+
+    /// A number: `?` for negative ones, then `0`-`9` for 1 to 10, or hex digits
+    /// written as `A`-`P` up to a `@`.
+    fn number(&mut self) -> Option<i64> {
+        let negative = self.eat(b"?");
+        let value = match self.next()? {
+            c @ b'0'..=b'9' => (c - b'0') as i64 + 1,
+            b'@' => 0,
+            mut c => {
+                let mut value = 0i64;
+                while c != b'@' {
+                    if !(b'A'..=b'P').contains(&c) {
+                        return None;
+                    }
+                    value = value.checked_mul(16)?.checked_add((c - b'A') as i64)?;
+                    c = self.next()?;
+                }
+                value
+            }
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    /// A variable: `0`-`2` are static members, `3` globals and `4` function
+    /// local statics.
+    fn variable(&mut self, kind: u8, name: &str) -> Option<String> {
+        let access = match kind {
+            b'0' => "private: static ",
+            b'1' => "protected: static ",
+            b'2' => "public: static ",
+            _ => "",
+        };
+        let ty = self.ty()?;
+        self.eat(b"E");
+        let cv = self.cv()?;
+        if self.options.no_return_type {
+            Some(name.to_string())
+        } else if ty.ends_with(['*', '&']) {
+            Some(format!("{}{}{}", access, ty, name))
+        } else {
+            Some(format!("{}{}{} {}", access, ty, cv, name))
+        }
+    }
+
+    fn function(&mut self, kind: u8, name: &str) -> Option<String> {
+        let (access, storage, member) = match kind {
+            b'A' | b'B' => ("private: ", "", true),
+            b'C' | b'D' => ("private: ", "static ", false),
+            b'E' | b'F' => ("private: ", "virtual ", true),
+            b'I' | b'J' => ("protected: ", "", true),
+            b'K' | b'L' => ("protected: ", "static ", false),
+            b'M' | b'N' => ("protected: ", "virtual ", true),
+            b'Q' | b'R' => ("public: ", "", true),
+            b'S' | b'T' => ("public: ", "static ", false),
+            b'U' | b'V' => ("public: ", "virtual ", true),
+            b'Y' | b'Z' => ("", "", false),
+            _ => return None,
+        };
+
+        let mut this_qualifiers = String::new();
+        if member {
+            self.pointer_modifiers();
+            let reference = if self.eat(b"G") {
+                " &"
+            } else if self.eat(b"H") {
+                " &&"
+            } else {
+                ""
+            };
+            this_qualifiers = self.cv()?.to_string() + reference;
+        }
+
+        let calling_convention = match self.next()? {
+            b'A' | b'B' => "__cdecl",
+            b'C' | b'D' => "__pascal",
+            b'E' | b'F' => "__thiscall",
+            b'G' | b'H' => "__stdcall",
+            b'I' | b'J' => "__fastcall",
+            b'Q' => "__vectorcall",
+            _ => return None,
+        };
+
+        let return_type = if self.eat(b"@") {
+            String::new()
+        } else {
+            let cv = if self.eat(b"?A") {
+                ""
+            } else if self.eat(b"?B") {
+                " const"
+            } else {
+                ""
+            };
+            format!("{}{} ", self.ty()?, cv)
+        };
+
+        let params = self.params()?;
+        // Throw specification
+        if !self.eat(b"Z") {
+            return None;
+        }
+
+        let signature = if self.options.no_params {
+            String::new()
+        } else {
+            format!("({}){}", params, this_qualifiers)
+        };
+        if self.options.no_return_type {
+            Some(format!("{}{}", name, signature))
+        } else {
+            Some(format!(
+                "{}{}{}{} {}{}",
+                access, storage, return_type, calling_convention, name, signature
+            ))
+        }
+    }
+
+    fn params(&mut self) -> Option<String> {
+        if self.eat(b"X") {
+            return Some("void".to_string());
+        }
+        let mut params = Vec::new();
+        loop {
+            if self.eat(b"@") {
+                break;
+            }
+            if self.eat(b"Z") {
+                params.push("...".to_string());
+                break;
+            }
+            if let Some(c @ b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+                params.push(self.params.get((c - b'0') as usize)?.clone());
+                continue;
+            }
+            let start = self.pos;
+            let ty = self.ty()?;
+            if self.pos - start > 1 && self.params.len() < MAX_BACK_REFERENCES {
+                self.params.push(ty.clone());
+            }
+            params.push(ty);
+        }
+        Some(params.join(", "))
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        let ty = match self.next()? {
+            b'C' => "signed char",
+            b'D' => "char",
+            b'E' => "unsigned char",
+            b'F' => "short",
+            b'G' => "unsigned short",
+            b'H' => "int",
+            b'I' => "unsigned int",
+            b'J' => "long",
+            b'K' => "unsigned long",
+            b'M' => "float",
+            b'N' => "double",
+            b'O' => "long double",
+            b'X' => "void",
+            b'_' => match self.next()? {
+                b'N' => "bool",
+                b'J' => "__int64",
+                b'K' => "unsigned __int64",
+                b'W' => "wchar_t",
+                b'S' => "char16_t",
+                b'U' => "char32_t",
+                b'Q' => "char8_t",
+                _ => return None,
+            },
+            b'T' => return Some(format!("union {}", self.type_name()?)),
+            b'U' => return Some(format!("struct {}", self.type_name()?)),
+            b'V' => return Some(format!("class {}", self.type_name()?)),
+            b'W' if self.eat(b"4") => return Some(format!("enum {}", self.type_name()?)),
+            b'P' => return self.pointer("*", ""),
+            b'Q' => return self.pointer("*", "const"),
+            b'R' => return self.pointer("*", "volatile"),
+            b'S' => return self.pointer("*", "const volatile"),
+            b'A' => return self.pointer("&", ""),
+            b'$' if self.eat(b"$Q") => return self.pointer("&&", ""),
+            b'$' if self.eat(b"$T") => "std::nullptr_t",
+            _ => return None,
+        };
+        Some(ty.to_string())
+    }
+
+    fn type_name(&mut self) -> Option<String> {
+        let mut scopes = vec![self.name_fragment()?];
+        scopes.extend(self.scopes()?);
+        scopes.reverse();
+        Some(scopes.join("::"))
+    }
+
+    /// `__ptr64`, `__restrict` and `__unaligned`, which `llvm-undname` doesn't
+    /// print either for 64-bit pointers.
+    fn pointer_modifiers(&mut self) {
+        while self.eat(b"E") || self.eat(b"I") || self.eat(b"F") {}
+    }
+
+    fn cv(&mut self) -> Option<&'static str> {
+        Some(match self.next()? {
+            b'A' => "",
+            b'B' => " const",
+            b'C' => " volatile",
+            b'D' => " const volatile",
+            _ => return None,
+        })
+    }
+
+    /// A pointer or reference `kind`, itself qualified with `cv`.
+    fn pointer(&mut self, kind: &str, cv: &str) -> Option<String> {
+        self.pointer_modifiers();
+        // Pointers to functions and members
+        if matches!(self.peek()?, b'6'..=b'9' | b'$') {
+            return None;
+        }
+        let mut pointee_cv = self.cv()?;
+        // Pointers carry their own qualifiers
+        if matches!(self.peek()?, b'P'..=b'S' | b'A') {
+            pointee_cv = "";
+        }
+        let pointee = self.ty()? + pointee_cv;
+        let separator = if pointee.ends_with(['*', '&']) {
+            ""
+        } else {
+            " "
+        };
+        Some(format!("{}{}{}{}", pointee, separator, kind, cv))
+    }
+}
+
+enum Operator {
+    Constructor,
+    Destructor,
+    Named(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::demangle::{demangle_text, DemangleMode};
+
+    /// Checked against `llvm-undname`.
+    #[test]
+    fn matches_llvm_undname() {
+        for (symbol, demangled) in [
+            ("?foo@@YAHH@Z", "int __cdecl foo(int)"),
+            ("?x@@3HA", "int x"),
+            ("?bar@ns@@YAXPEAD@Z", "void __cdecl ns::bar(char *)"),
+            ("?f@@YANMN@Z", "double __cdecl f(float, double)"),
+            (
+                "?g@@YAXPEBDPEBD@Z",
+                "void __cdecl g(char const *, char const *)",
+            ),
+            ("?h@@YA_NAEBVFoo@@@Z", "bool __cdecl h(class Foo const &)"),
+            (
+                "?get@?$Box@H@@QEAAHXZ",
+                "public: int __cdecl Box<int>::get(void)",
+            ),
+            ("??0Foo@@QEAA@XZ", "public: __cdecl Foo::Foo(void)"),
+        ] {
+            assert_eq!(demangle_text(symbol, Some(DemangleMode::Full)), demangled);
+        }
+    }
+
+    #[test]
+    fn modes() {
+        let call = "call i32 @\"?get@?$Box@H@@QEAAHXZ\"(ptr %b)";
+        assert_eq!(
+            demangle_text(call, Some(DemangleMode::NoParams)),
+            "call i32 @\"public: int __cdecl Box<int>::get\"(ptr %b)"
+        );
+        assert_eq!(
+            demangle_text(call, Some(DemangleMode::Short)),
+            "call i32 @\"Box<int>::get\"(ptr %b)"
+        );
+    }
+
+    #[test]
+    fn leaves_truncated_symbols_alone() {
+        assert_eq!(
+            demangle_text("?foo@@YAHH", Some(DemangleMode::Full)),
+            "?foo@@YAHH"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! Swift symbols (`$s4main3fooyS2iF`), printed like `swift-demangle` does.
+//!
+//! Swift mangling is postfix: operands are pushed on a stack and the operators
+//! after them combine them. Covers functions, variables and their accessors with
+//! nominal, standard library, tuple and generic types. Symbols using anything
+//! else (generic signatures, specializations, thunks, ...) are left mangled.
+
+use super::{at_identifier_start, token_len, DemangleOptions, Demangler};
+
+pub struct Swift;
+
+impl Demangler for Swift {
+    fn prefix_len(&self, prev: Option<u8>, text: &[u8]) -> Option<usize> {
+        if !at_identifier_start(prev) {
+            return None;
+        }
+        match text {
+            [b'$', b's' | b'S' | b'e', ..] => Some(2),
+            [b'_', b'$', b's' | b'S' | b'e', ..] => Some(3),
+            _ => None,
+        }
+    }
+
+    fn demangle<'a>(
+        &self,
+        text: &'a [u8],
+        options: &DemangleOptions,
+    ) -> Option<(String, &'a [u8])> {
+        let len = token_len(text, |c| {
+            c.is_ascii_alphanumeric() || matches!(c, b'_' | b'$')
+        });
+        let prefix = self.prefix_len(None, text)?;
+        let mut parser = Parser {
+            text: &text[prefix..len],
+            options: *options,
+            pos: 0,
+            stack: Vec::new(),
+            substitutions: Vec::new(),
+        };
+        let demangled = parser.symbol()?;
+        Some((demangled, &text[len..]))
+    }
+}
+
+#[derive(Clone)]
+enum Node {
+    Identifier(String),
+    Type(String),
+    Tuple(Vec<String>),
+    /// `y`, an empty list
+    EmptyList,
+    /// `_`, marking the end of the first element of a list
+    FirstElement,
+    Entity(String),
+}
+
+impl Node {
+    /// The node as the context of a name: a module or a type.
+    fn context(self) -> Option<String> {
+        match self {
+            Node::Identifier(name) | Node::Type(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn ty(self) -> Option<String> {
+        match self {
+            Node::Type(ty) => Some(ty),
+            Node::Tuple(elements) => Some(format!("({})", elements.join(", "))),
+            Node::EmptyList => Some("()".to_string()),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    options: DemangleOptions,
+    pos: usize,
+    stack: Vec<Node>,
+    /// Identifiers and types, for substitutions
+    substitutions: Vec<Node>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn pop(&mut self) -> Option<Node> {
+        self.stack.pop()
+    }
+
+    fn push_substitutable(&mut self, node: Node) {
+        self.substitutions.push(node.clone());
+        self.stack.push(node);
+    }
+
+    fn symbol(&mut self) -> Option<String> {
+        while let Some(c) = self.next() {
+            match c {
+                b'0'..=b'9' => {
+                    self.pos -= 1;
+                    let identifier = self.identifier()?;
+                    self.push_substitutable(Node::Identifier(identifier));
+                }
+                b'y' => self.stack.push(Node::EmptyList),
+                b'_' => self.stack.push(match self.stack.last()? {
+                    Node::Identifier(_) | Node::FirstElement => {
+                        // An empty argument label
+                        Node::Identifier("_".to_string())
+                    }
+                    _ => Node::FirstElement,
+                }),
+                b's' => self.stack.push(Node::Identifier("Swift".to_string())),
+                b'S' => self.standard_type()?,
+                b'A' => self.substitution()?,
+                b't' => {
+                    let elements = self.pop_list()?;
+                    self.stack.push(Node::Tuple(elements));
+                }
+                b'V' | b'C' | b'O' | b'P' | b'a' => {
+                    let Node::Identifier(name) = self.pop()? else {
+                        return None;
+                    };
+                    let context = self.pop()?.context()?;
+                    self.push_substitutable(Node::Type(format!("{}.{}", context, name)));
+                }
+                b'G' => {
+                    let args = self.pop_list()?;
+                    let ty = self.pop()?.ty()?;
+                    self.push_substitutable(Node::Type(format!("{}<{}>", ty, args.join(", "))));
+                }
+                b'F' => self.function()?,
+                b'v' => self.variable()?,
+                _ => return None,
+            }
+        }
+        match &self.stack[..] {
+            [Node::Entity(entity)] => Some(entity.clone()),
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let len = token_len(&self.text[self.pos..], |c| c.is_ascii_digit());
+        let number = std::str::from_utf8(&self.text[self.pos..self.pos.checked_add(len)?])
+            .ok()?
+            .parse()
+            .ok()?;
+        self.pos += len;
+        Some(number)
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        // Word substitutions and punycode start with a 0
+        if self.peek()? == b'0' {
+            return None;
+        }
+        let len = self.number()?;
+        // The length comes from the symbol, so it may be anything
+        let end = self.pos.checked_add(len)?;
+        let identifier = self.text.get(self.pos..end)?;
+        self.pos += len;
+        Some(std::str::from_utf8(identifier).ok()?.to_string())
+    }
+
+    /// `S`, followed by a standard library type, optionally repeated, or by `g` for
+    /// an optional.
+    fn standard_type(&mut self) -> Option<()> {
+        if self.peek()? == b'g' {
+            self.pos += 1;
+            let ty = self.pop()?.ty()?;
+            self.stack.push(Node::Type(format!("{}?", ty)));
+            return Some(());
+        }
+        let repeat = if self.peek()?.is_ascii_digit() {
+            self.number()?
+        } else {
+            1
+        };
+        let ty = match self.next()? {
+            b'a' => "Array",
+            b'b' => "Bool",
+            b'c' => "UnicodeScalar",
+            b'D' => "Dictionary",
+            b'd' => "Double",
+            b'f' => "Float",
+            b'h' => "Set",
+            b'i' => "Int",
+            b'J' => "Character",
+            b'N' => "ClosedRange",
+            b'n' => "Range",
+            b'P' => "UnsafePointer",
+            b'p' => "UnsafeMutablePointer",
+            b'q' => "Optional",
+            b'R' => "UnsafeBufferPointer",
+            b'r' => "UnsafeMutableBufferPointer",
+            b'S' => "String",
+            b's' => "Substring",
+            b'u' => "UInt",
+            b'V' => "UnsafeRawPointer",
+            b'v' => "UnsafeMutableRawPointer",
+            _ => return None,
+        };
+        for _ in 0..repeat {
+            self.stack.push(Node::Type(format!("Swift.{}", ty)));
+        }
+        Some(())
+    }
+
+    /// `A`, followed by lowercase letters for substitutions and an uppercase one
+    /// for the last substitution, each optionally repeated, or by an index
+    /// ending in `_`.
+    fn substitution(&mut self) -> Option<()> {
+        let mut count = None;
+        loop {
+            match self.peek()? {
+                b'0'..=b'9' => count = Some(self.number()?),
+                b'_' => {
+                    self.pos += 1;
+                    // `A_` is the 27th substitution, `A0_` the 28th
+                    let index = count.map_or(Some(26), |n| n.checked_add(27))?;
+                    let node = self.substitutions.get(index)?.clone();
+                    self.stack.push(node);
+                    return Some(());
+                }
+                c @ (b'a'..=b'z' | b'A'..=b'Z') => {
+                    self.pos += 1;
+                    let index = (c.to_ascii_lowercase() - b'a') as usize;
+                    let node = self.substitutions.get(index)?.clone();
+                    for _ in 0..count.unwrap_or(1).max(1) {
+                        self.stack.push(node.clone());
+                    }
+                    if c.is_ascii_uppercase() {
+                        return Some(());
+                    }
+                    count = None;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// The elements of a tuple or generic argument list: types up to an empty
+    /// list or the one marked as the first.
+    fn pop_list(&mut self) -> Option<Vec<String>> {
+        let mut elements = Vec::new();
+        loop {
+            match self.pop()? {
+                Node::EmptyList => break,
+                Node::FirstElement => {
+                    elements.push(self.pop()?.ty()?);
+                    break;
+                }
+                node => elements.push(node.ty()?),
+            }
+        }
+        elements.reverse();
+        Some(elements)
+    }
+
+    /// A function: its context, name, argument labels, result and parameters.
+    fn function(&mut self) -> Option<()> {
+        let params = match self.pop()? {
+            Node::EmptyList => Vec::new(),
+            Node::Tuple(elements) => elements,
+            node => vec![node.ty()?],
+        };
+        let result = self.pop()?.ty()?;
+        let labels = if let Some(Node::EmptyList) = self.stack.last() {
+            self.pop();
+            None
+        } else {
+            let mut labels = Vec::new();
+            for _ in 0..params.len() {
+                let Node::Identifier(label) = self.pop()? else {
+                    return None;
+                };
+                labels.push(label);
+            }
+            labels.reverse();
+            Some(labels)
+        };
+        let Node::Identifier(name) = self.pop()? else {
+            return None;
+        };
+        let context = self.pop()?.context()?;
+
+        let params = match labels {
+            Some(labels) if !params.is_empty() => params
+                .into_iter()
+                .zip(labels)
+                .map(|(ty, label)| format!("{}: {}", label, ty))
+                .collect::<Vec<_>>(),
+            _ => params,
+        };
+        // The result is part of the signature
+        let signature = if self.options.no_params {
+            String::new()
+        } else if self.options.no_return_type {
+            format!("({})", params.join(", "))
+        } else {
+            format!("({}) -> {}", params.join(", "), result)
+        };
+        self.stack
+            .push(Node::Entity(format!("{}.{}{}", context, name, signature)));
+        Some(())
+    }
+
+    /// A variable, or one of its accessors.
+    fn variable(&mut self) -> Option<()> {
+        let accessor = match self.peek() {
+            // The storage itself, as for global variables
+            Some(b'p') => Some(""),
+            Some(b'g') => Some(".getter"),
+            Some(b's') => Some(".setter"),
+            Some(b'M') => Some(".modify"),
+            Some(b'r') => Some(".read"),
+            _ => None,
+        };
+        if accessor.is_some() {
+            self.pos += 1;
+        }
+        let accessor = accessor.unwrap_or_default();
+        let ty = self.pop()?.ty()?;
+        let Node::Identifier(name) = self.pop()? else {
+            return None;
+        };
+        let context = self.pop()?.context()?;
+        let entity = if self.options.no_return_type {
+            format!("{}.{}{}", context, name, accessor)
+        } else {
+            format!("{}.{}{} : {}", context, name, accessor, ty)
+        };
+        self.stack.push(Node::Entity(entity));
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::demangle::{demangle_text, DemangleMode};
+
+    #[test]
+    fn functions() {
+        for (symbol, demangled) in [
+            ("$s4main3fooyS2iF", "main.foo(Swift.Int) -> Swift.Int"),
+            ("$s4main3fooyySSF", "main.foo(Swift.String) -> ()"),
+            (
+                "$s4main3barySi_SdtSS_SbtF",
+                "main.bar(Swift.String, Swift.Bool) -> (Swift.Int, Swift.Double)",
+            ),
+            (
+                "$s4main3fooyAA5PointVSaySiGF",
+                "main.foo(Swift.Array<Swift.Int>) -> main.Point",
+            ),
+            // Apple platforms add an underscore
+            ("_$s4main3fooyS2iF", "main.foo(Swift.Int) -> Swift.Int"),
+        ] {
+            assert_eq!(demangle_text(symbol, Some(DemangleMode::Full)), demangled);
+        }
+        for mode in [DemangleMode::NoParams, DemangleMode::Short] {
+            assert_eq!(demangle_text("$s4main3fooyS2iF", Some(mode)), "main.foo");
+        }
+    }
+
+    #[test]
+    fn variables_and_accessors() {
+        for (symbol, demangled, short) in [
+            ("$s4main1xSivp", "main.x : Swift.Int", "main.x"),
+            (
+                "$s4main1xSivg",
+                "main.x.getter : Swift.Int",
+                "main.x.getter",
+            ),
+            (
+                "$s4main5PointV1xSivs",
+                "main.Point.x.setter : Swift.Int",
+                "main.Point.x.setter",
+            ),
+        ] {
+            assert_eq!(demangle_text(symbol, Some(DemangleMode::Full)), demangled);
+            assert_eq!(demangle_text(symbol, Some(DemangleMode::Short)), short);
+        }
+    }
+
+    #[test]
+    fn substitutions_past_the_letters() {
+        let types: String = ('a'..='l').map(|c| format!("AA1{}V", c)).collect();
+        let names: Vec<_> = ('a'..='l').map(|c| format!("main.{}", c)).collect();
+        // main, foo, Swift.Array<Swift.Int>, then a name and a type for each
+        // struct, so that main.l is the 27th
+        let symbol = format!("$s4main3fooyySaySiG_{}A_tF", types);
+        assert_eq!(
+            demangle_text(&symbol, Some(DemangleMode::Full)),
+            format!(
+                "main.foo(Swift.Array<Swift.Int>, {}, main.l) -> ()",
+                names.join(", ")
+            )
+        );
+        // Without the array, main.m is the 28th
+        let symbol = format!("$s4main3fooyy{}AA1mVA0_tF", types.replacen('V', "V_", 1));
+        assert_eq!(
+            demangle_text(&symbol, Some(DemangleMode::Full)),
+            format!("main.foo({}, main.m, main.m) -> ()", names.join(", "))
+        );
+    }
+
+    #[test]
+    fn leaves_truncated_symbols_alone() {
+        assert_eq!(
+            demangle_text("@$s4main3foo", Some(DemangleMode::Full)),
+            "@$s4main3foo"
+        );
+        // An identifier length that overflows the position
+        assert_eq!(
+            demangle_text("@$s18446744073709551615x", Some(DemangleMode::Full)),
+            "@$s18446744073709551615x"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Self-contained static HTML report for `--html`.
+//!
+//! Everything is inlined into a single file without any scripts, so reports can
+//! be attached to reviews and opened offline.
+
+use crate::demangle::{BodyCache, DemangleMode};
+use crate::optpipeline::Pass;
+use similar::{DiffOp, TextDiff};
+use std::io::{self, Write};
+
+const STYLE: &str = r#"
+* { box-sizing: border-box; }
+body { margin: 0; font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; color: #1f2328; }
+nav { position: fixed; top: 0; bottom: 0; left: 0; width: 300px; overflow: auto; padding: 1em; background: #f6f8fa; border-right: 1px solid #d0d7de; }
+nav h1 { font-size: 1.2em; margin: 0; }
+nav .source { color: #656d76; word-break: break-all; }
+nav ul { list-style: none; padding: 0; }
+nav li { margin: 0.2em 0; word-break: break-all; }
+nav .count { color: #656d76; font-size: 0.85em; }
+main { margin-left: 300px; padding: 1em 2em; }
+a { color: #0969da; text-decoration: none; }
+a:hover { text-decoration: underline; }
+pre, table.diff { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 12px; }
+section.function { border-bottom: 2px solid #d0d7de; padding-bottom: 1em; }
+ol.passes { columns: 3; font-size: 0.9em; }
+ol.passes li.unchanged a { color: #8c959f; }
+ol.passes li.changed a { font-weight: bold; }
+ol.passes li.crashed a { font-weight: bold; color: #cf222e; }
+article.pass { margin: 1.5em 0; }
+article.pass h3 { font-size: 1em; background: #f6f8fa; border: 1px solid #d0d7de; padding: 0.4em 0.6em; margin: 0; }
+article.pass h3.changed::before { content: "\25CF  "; color: #bf8700; }
+article.pass h3.crashed { color: #cf222e; border-color: #cf222e; }
+p.unchanged { color: #656d76; margin: 0.4em 0.6em; }
+p.crashed { color: #cf222e; margin: 0.4em 0.6em; }
+pre.crashed { border: 1px solid #d0d7de; border-top: 0; margin: 0; padding: 0.5em; white-space: pre-wrap; word-break: break-all; }
+table.diff { width: 100%; border-collapse: collapse; table-layout: fixed; border: 1px solid #d0d7de; border-top: 0; }
+table.diff td { padding: 0 0.5em; white-space: pre-wrap; word-break: break-all; vertical-align: top; }
+table.diff td.ln { width: 4em; text-align: right; color: #8c959f; user-select: none; }
+table.diff td.del { background: #ffebe9; }
+table.diff td.add { background: #e6ffec; }
+table.diff td.empty { background: #f6f8fa; }
+table.diff tr.hunk td { background: #ddf4ff; color: #656d76; }
+"#;
+
+/// Write a report for `functions` (with their selected passes) to `out`.
+pub fn write_report<W: Write>(
+    out: &mut W,
+    title: &str,
+    prefix: &str,
+    functions: &[(String, Vec<(usize, &Pass)>)],
+    demangle: Option<DemangleMode>,
+) -> io::Result<()> {
+    let bodies = BodyCache::new(demangle);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>optdiff: {}</title>", escape(title))?;
+    writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+
+    writeln!(out, "<nav>\n<h1>optdiff</h1>")?;
+    writeln!(out, "<p class=\"source\">{}</p>\n<ul>", escape(title))?;
+    for (f, (func_name, passes)) in functions.iter().enumerate() {
+        let changed = passes.iter().filter(|(_, pass)| pass.ir_changed).count();
+        writeln!(
+            out,
+            "<li><a href=\"#f{}\">{}</a> <span class=\"count\">{}/{} changed</span></li>",
+            f,
+            escape(func_name),
+            changed,
+            passes.len()
+        )?;
+    }
+    writeln!(out, "</ul>\n</nav>\n<main>")?;
+
+    if !prefix.trim().is_empty() {
+        writeln!(
+            out,
+            "<details><summary>Compiler output</summary><pre>{}</pre></details>",
+            escape(prefix)
+        )?;
+    }
+
+    for (f, (func_name, passes)) in functions.iter().enumerate() {
+        writeln!(out, "<section class=\"function\" id=\"f{}\">", f)?;
+        writeln!(out, "<h2>{}</h2>\n<ol class=\"passes\">", escape(func_name))?;
+        for (i, pass) in passes {
+            writeln!(
+                out,
+                "<li value=\"{}\" class=\"{}\"><a href=\"#f{}-p{}\">{}</a></li>",
+                i + 1,
+                changed_class(pass),
+                f,
+                i,
+                escape(&pass.name)
+            )?;
+        }
+        writeln!(out, "</ol>")?;
+
+        for (i, pass) in passes {
+            writeln!(out, "<article class=\"pass\" id=\"f{}-p{}\">", f, i)?;
+            writeln!(
+                out,
+                "<h3 class=\"{}\">({}·{}) {}{}</h3>",
+                changed_class(pass),
+                i + 1,
+                escape(func_name),
+                escape(&pass.name),
+                if pass.crashed { " (crashed)" } else { "" }
+            )?;
+            if pass.crashed {
+                writeln!(
+                    out,
+                    "<p class=\"crashed\">The dump ends in this pass, the compiler probably crashed in it. The IR it was given:</p>"
+                )?;
+                let before = bodies.demangle(&pass.before);
+                writeln!(out, "<pre class=\"crashed\">{}</pre>", escape(&before))?;
+            } else if pass.ir_changed {
+                let before = format!("{}\n", bodies.demangle(&pass.before));
+                let after = format!("{}\n", bodies.demangle(&pass.after));
+                write_side_by_side(out, &TextDiff::from_lines(&before, &after))?;
+            } else {
+                writeln!(out, "<p class=\"unchanged\">No changes to the IR</p>")?;
+            }
+            writeln!(out, "</article>")?;
+        }
+        writeln!(out, "</section>")?;
+    }
+
+    writeln!(out, "</main>\n</body>\n</html>")
+}
+
+fn changed_class(pass: &Pass) -> &'static str {
+    if pass.crashed {
+        "crashed"
+    } else if pass.ir_changed {
+        "changed"
+    } else {
+        "unchanged"
+    }
+}
+
+/// Render `diff` as a two column table, with the same context as the unified diffs.
+fn write_side_by_side<W: Write>(out: &mut W, diff: &TextDiff<'_, '_, '_, str>) -> io::Result<()> {
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    writeln!(out, "<table class=\"diff\">")?;
+    for group in diff.grouped_ops(10) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        writeln!(
+            out,
+            "<tr class=\"hunk\"><td colspan=\"4\">@@ -{},{} +{},{} @@</td></tr>",
+            old_range.start + 1,
+            old_range.len(),
+            new_range.start + 1,
+            new_range.len()
+        )?;
+
+        for op in &group {
+            let (old_range, new_range) = (op.old_range(), op.new_range());
+            let rows = match op {
+                DiffOp::Equal { .. } => old_range.len(),
+                _ => old_range.len().max(new_range.len()),
+            };
+            let (old_class, new_class) = match op {
+                DiffOp::Equal { .. } => ("", ""),
+                _ => ("del", "add"),
+            };
+            for row in 0..rows {
+                let old = (row < old_range.len()).then(|| old_range.start + row);
+                let new = (row < new_range.len()).then(|| new_range.start + row);
+                write!(out, "<tr>")?;
+                write_cell(out, old.map(|idx| (idx, old_lines[idx])), old_class)?;
+                write_cell(out, new.map(|idx| (idx, new_lines[idx])), new_class)?;
+                writeln!(out, "</tr>")?;
+            }
+        }
+    }
+    writeln!(out, "</table>")
+}
+
+fn write_cell<W: Write>(out: &mut W, line: Option<(usize, &str)>, class: &str) -> io::Result<()> {
+    match line {
+        Some((idx, line)) => write!(
+            out,
+            "<td class=\"ln\">{}</td><td class=\"{}\">{}</td>",
+            idx + 1,
+            class,
+            escape(line.trim_end_matches(['\n', '\r']))
+        ),
+        None => write!(out, "<td class=\"ln\"></td><td class=\"empty\"></td>"),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
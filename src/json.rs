@@ -0,0 +1,35 @@
+//! Serializable form of the pass pipeline for `--output json`.
+//!
+//! The schema is documented in the README; bump [`SCHEMA_VERSION`] on any
+//! incompatible change.
+
+use serde::Serialize;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub version: u32,
+    /// Compiler output preceding the first pass dump
+    pub prefix: &'a str,
+    pub functions: Vec<Function>,
+}
+
+#[derive(Serialize)]
+pub struct Function {
+    pub name: String,
+    pub passes: Vec<Pass>,
+}
+
+#[derive(Serialize)]
+pub struct Pass {
+    /// 1-based position in the function's full pipeline, as shown in diff titles
+    pub index: usize,
+    pub name: String,
+    pub machine: bool,
+    pub before: String,
+    pub after: String,
+    pub ir_changed: bool,
+    /// The dump ends in the middle of this pass, e.g. because the compiler crashed
+    pub crashed: bool,
+}
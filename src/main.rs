@@ -1,27 +1,52 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use color_eyre::{
     eyre::{eyre, WrapErr},
-    Result,
+    Report, Result, Section,
 };
 use color_print::cformat;
+use compare::AlignedPass;
+use config::{Config, RuleKind};
+use demangle::{demangle_text, BodyCache, DemangleMode};
 use is_terminal::IsTerminal;
 use itertools::Itertools;
 use memchr::memmem;
-use optpipeline::Pass;
+use memmap2::Mmap;
+use optpipeline::{MissingIr, OptPipelineBackendOptions, OptPipelineResults, Pass, PassDumpError};
+use range::{PassRange, PassSelector};
+use rayon::prelude::*;
 use regex::Regex;
+use reproduce::Reproducer;
 use similar::TextDiff;
-use std::path::PathBuf;
+use std::fs::File;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::{
-    collections::HashSet,
-    io::{self, Read, Write},
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, BufRead, BufReader, Read, Write},
 };
 
 #[cfg(unix)]
 use pager::Pager;
 
 mod cli_write;
+mod compare;
+mod config;
 mod demangle;
+mod html;
+mod json;
 mod optpipeline;
+mod range;
+mod reproduce;
+mod tui;
+mod wrapper;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Unified diff of every pass, for reading in a pager
+    Diff,
+    /// The parsed pipeline as JSON, see the README for the schema
+    Json,
+}
 
 #[derive(Parser)]
 #[command(
@@ -36,6 +61,11 @@ mod optpipeline;
    <i># View optimization changes:</i>
    clang input.c -O2 -mllvm -print-before-all -mllvm -print-after-all -c -o /dev/null 2>&1 | optdiff
 
+   <i># Or let optdiff run the compiler (clang, opt or rustc) with the right flags:</i>
+   optdiff -- clang input.c -O2
+   optdiff -f foo -- opt -O2 input.ll
+   optdiff -- rustc -O --crate-type=lib input.rs
+
    <i># To limit output to a specific function:</i>
    clang input.c -O2 -mllvm -print-before-all -mllvm -print-after-all -mllvm -filter-print-funcs=foo -c -o /dev/null 2>&1 | optdiff
 
@@ -43,10 +73,28 @@ mod optpipeline;
    clang input.c -O2 -mllvm -print-before-all -mllvm -print-after-all -c -o /dev/null &> dump.txt
    optdiff dump.txt
 
+   <i># Track global/variable changes with module-scope dumps:</i>
+   clang input.c -O2 -mllvm -print-before-all -mllvm -print-after-all -mllvm -print-module-scope -c -o /dev/null 2>&1 | optdiff -f '{}'
+
+   <i># Export the pipeline for scripts:</i>
+   optdiff dump.txt -o json | jq '.functions[].name'
+
+   <i># Share an investigation as a single HTML file:</i>
+   optdiff dump.txt -f foo --html report.html
+
+   <i># What did the loop passes do, all in one diff?</i>
+   optdiff dump.txt -f foo --squash --from LoopSimplifyPass --to LoopDeletionPass
+
+   <i># What did my LLVM patch change in the pipeline?</i>
+   optdiff --compare old-dump.txt new-dump.txt -s
+
    <i># To filter functions/passes (and optionally with regex `-E`):</i>
+   optdiff dump.txt -f 'foo*' -f bar           # match functions starting with 'foo', and 'bar'
    optdiff dump.txt -E -f 'foo.*'              # match functions starting with 'foo'
    optdiff dump.txt -E -P 'Combine|Simplify'   # match passes containing 'Combine' or 'Simplify'
-   optdiff dump.txt -E -f '^main$' -P '.*Opt$' # match exactly 'main' function and passes ending in 'Opt'"))]
+   optdiff dump.txt -X Analysis                # hide passes containing 'Analysis'
+   optdiff dump.txt --from SROAPass --to 40    # only show passes from the first SROAPass to pass 40
+   optdiff dump.txt -E -f '^main$' -P '.*Opt$' # match exactly 'main' function and passes ending in 'Opt'", optpipeline::FULL_MODULE))]
 struct Args {
     /// Path to LLVM pass dump file. If not provided, reads from stdin
     #[arg(value_name = "FILE")]
@@ -56,15 +104,33 @@ struct Args {
     #[arg(short = 's', long = "skip-unchanged")]
     skip_unchanged: bool,
 
-    /// Only show passes for specified function
+    /// Only show passes for functions matching this name or glob pattern (repeatable)
     #[arg(short = 'f', long = "function")]
-    function: Option<String>,
+    function: Vec<String>,
 
     /// Only show passes with names containing this string
     #[arg(short = 'P', long = "pass")]
     pass: Option<String>,
 
-    /// Enable extended regex patterns for -f and -P
+    /// Hide passes with names containing this string
+    #[arg(short = 'X', long = "pass-exclude")]
+    pass_exclude: Option<String>,
+
+    /// Start at this pass, given by its index in the diff titles or its name, with
+    /// `#N` for the Nth run of the pass, like `InstCombinePass#3`
+    #[arg(long = "from", value_name = "PASS")]
+    from: Option<PassSelector>,
+
+    /// End at this pass, given like `--from`
+    #[arg(long = "to", value_name = "PASS")]
+    to: Option<PassSelector>,
+
+    /// Show a single diff per function, from the IR before the first selected pass
+    /// to the IR after the last
+    #[arg(long = "squash", conflicts_with_all = ["compare", "tui"])]
+    squash: bool,
+
+    /// Enable extended regex patterns for -f, -P and -X
     #[arg(short = 'E', long = "extended-regex")]
     extended_regex: bool,
 
@@ -72,9 +138,16 @@ struct Args {
     #[arg(short = 'l', long = "list")]
     list: bool,
 
-    /// Demangle C++ symbols
-    #[arg(short = 'd', long = "demangle")]
-    demangle: bool,
+    /// Demangle C++, Rust, Swift and D symbols
+    #[arg(
+        short = 'd',
+        long = "demangle",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "full"
+    )]
+    demangle: Option<DemangleMode>,
 
     /// Which pager to use
     #[arg(short = 'p', long = "pager", env = "OPTDIFF_PAGER")]
@@ -83,19 +156,355 @@ struct Args {
     /// Pass through prefix
     #[arg(long = "passthrough")]
     passthrough: bool,
+
+    /// Treat the input as a `-print-module-scope` dump, grouping passes by the function
+    /// they ran on and showing module passes as `<Full Module>`. Detected automatically
+    #[arg(short = 'M', long = "full-module")]
+    full_module: bool,
+
+    /// Diff every pass dump against the one before it, for dumps of a few selected
+    /// passes (`-print-after=instcombine,gvn`). Passes are named after the span, like
+    /// `after InstCombinePass → after GVNPass`
+    #[arg(long = "snapshots")]
+    snapshots: bool,
+
+    /// Skip malformed pass dumps with a warning instead of failing, e.g. in logs cut
+    /// short by a crashing compiler
+    #[arg(long = "lenient")]
+    lenient: bool,
+
+    /// Write the IR given to the pass the dump ends in, e.g. because the compiler
+    /// crashed in it, to FILE for reproducing the crash with `opt`
+    #[arg(long = "crash-ir", value_name = "FILE", conflicts_with = "compare")]
+    crash_ir: Option<PathBuf>,
+
+    /// Write the IR before pass N of the selected function, as numbered in diff
+    /// titles, to a self-contained `.ll` file for reproducing the pass with `opt`
+    #[arg(
+        long = "extract",
+        value_name = "N",
+        conflicts_with_all = ["compare", "list", "output", "html", "tui", "crash_ir"]
+    )]
+    extract: Option<usize>,
+
+    /// File to write the IR extracted with `--extract` to [default: pass-N.ll]
+    #[arg(long = "extract-to", value_name = "FILE", requires = "extract")]
+    extract_to: Option<PathBuf>,
+
+    /// Also write the IR after the extracted pass, to the same file name with
+    /// `-after` added
+    #[arg(long = "extract-after", requires = "extract")]
+    extract_after: bool,
+
+    /// Keep debug info (`!dbg` attachments, `llvm.dbg.*` calls, `DBG_` instructions)
+    #[arg(long = "keep-debug-info")]
+    keep_debug_info: bool,
+
+    /// Keep metadata attachments such as `!tbaa` and `!range`
+    #[arg(long = "keep-metadata")]
+    keep_metadata: bool,
+
+    /// Keep function attributes, `attributes #N` groups and `declare` lines
+    #[arg(long = "keep-attributes")]
+    keep_attributes: bool,
+
+    /// Show the IR exactly as dumped, without any filtering
+    #[arg(long = "no-filter")]
+    no_filter: bool,
+
+    /// Enable a filter rule from the config file (can be repeated)
+    #[arg(short = 'r', long = "rule", value_name = "NAME")]
+    rules: Vec<String>,
+
+    /// Config file with filter rules. Defaults to the nearest `.optdiff.toml`
+    #[arg(long = "config", env = "OPTDIFF_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Output format
+    #[arg(short = 'o', long = "output", value_enum, default_value_t = OutputFormat::Diff)]
+    output: OutputFormat,
+
+    /// Write a self-contained HTML report to FILE instead of printing diffs
+    #[arg(long = "html", value_name = "FILE", conflicts_with = "output")]
+    html: Option<PathBuf>,
+
+    /// Browse functions, passes and diffs in an interactive terminal UI
+    #[arg(long = "tui", conflicts_with_all = ["output", "html"])]
+    tui: bool,
+
+    /// Compare the pipelines of two dumps, e.g. from compilers with and without a patch,
+    /// showing where the IR after each pass diverges
+    #[arg(
+        long = "compare",
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["input", "list", "output", "html", "tui"]
+    )]
+    compare: Option<Vec<PathBuf>>,
+
+    /// Number of threads to render diffs with. Defaults to the number of CPUs
+    #[arg(short = 'j', long = "jobs", value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Run this clang, opt or rustc command with the pass dump flags added, instead
+    /// of reading a dump
+    #[arg(last = true, value_name = "COMMAND", conflicts_with_all = ["input", "compare"])]
+    command: Vec<String>,
 }
 
-fn read_input(args: &Args) -> Result<String, io::Error> {
-    match &args.input {
-        Some(path) => std::fs::read_to_string(path),
+/// The text of a dump. Regular files are memory-mapped rather than read, so that
+/// the dump is paged in as it is parsed instead of being copied into memory up
+/// front. Lines are checked to be UTF-8 as they are parsed.
+enum DumpText {
+    Mapped(Mmap),
+    Owned(String),
+}
+
+impl Deref for DumpText {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            DumpText::Mapped(map) => map,
+            DumpText::Owned(text) => text.as_bytes(),
+        }
+    }
+}
+
+fn read_input(input: Option<&Path>) -> Result<DumpText> {
+    match input {
+        Some(path) => {
+            let mut file = File::open(path)
+                .wrap_err_with(|| format!("Failed to read from file: {}", path.display()))?;
+            let is_file = file.metadata().is_ok_and(|metadata| metadata.is_file());
+            if !is_file {
+                // Pipes like `<(cat dump.txt)` can't be mapped
+                let mut buffer = String::new();
+                file.read_to_string(&mut buffer)
+                    .wrap_err_with(|| format!("Failed to read from file: {}", path.display()))?;
+                return Ok(DumpText::Owned(buffer));
+            }
+            // SAFETY: the map is only read. Like any mmap, truncating the file while
+            // optdiff runs is not supported. Appending to it is, as the map doesn't
+            // grow with the file
+            let map = unsafe { Mmap::map(&file) }
+                .wrap_err_with(|| format!("Failed to read from file: {}", path.display()))?;
+            Ok(DumpText::Mapped(map))
+        }
         None => {
             let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer)?;
-            Ok(buffer)
+            io::stdin()
+                .read_to_string(&mut buffer)
+                .wrap_err("Failed to read from stdin")?;
+            Ok(DumpText::Owned(buffer))
+        }
+    }
+}
+
+fn open_input(input: Option<&Path>) -> Result<Box<dyn BufRead>> {
+    match input {
+        Some(path) => {
+            let file = File::open(path)
+                .wrap_err_with(|| format!("Failed to read from file: {}", path.display()))?;
+            Ok(Box::new(BufReader::new(file)))
         }
+        None => Ok(Box::new(io::stdin().lock())),
     }
 }
 
+/// Run the compiler command given after `--` and return its pass dump.
+fn run_compiler(args: &Args) -> Result<String> {
+    // `-filter-print-funcs` needs exact mangled names
+    let patterns = function_patterns(args)?;
+    let functions = match args.demangle {
+        None if patterns
+            .iter()
+            .all(|pattern| matches!(pattern, FunctionPattern::Name(_))) =>
+        {
+            args.function.clone()
+        }
+        _ => Vec::new(),
+    };
+    let flags = wrapper::DumpFlags {
+        functions: &functions,
+        full_module: args.full_module,
+    };
+    let output = wrapper::run(&args.command, &flags)?;
+
+    // Keep whatever the compiler printed to stdout out of the diffs
+    cli_write!(io::stderr(), "{}", output.stdout)?;
+    if !output.status.success() {
+        cli_writeln!(
+            io::stderr(),
+            "warning: '{}' failed ({}), showing the passes dumped so far",
+            args.command[0],
+            output.status
+        )?;
+    }
+    Ok(output.dump)
+}
+
+const MISSING_DUMP: &str =
+    "Did you forget to add `-mllvm -print-before-all -mllvm -print-after-all` or `-mllvm -print-changed`?";
+
+fn check_dump(dump: &[u8]) -> Result<()> {
+    // `-print-after-all` and `-print-changed` dumps only have "IR Dump After"
+    // headers, `-print-before-all` dumps only "IR Dump Before" headers
+    if memmem::find(dump, b"IR Dump Before").is_none()
+        && memmem::find(dump, b"IR Dump After").is_none()
+    {
+        return Err(eyre!(MISSING_DUMP));
+    }
+
+    Ok(())
+}
+
+/// Tell the user which side of the passes is reconstructed from their neighbours.
+fn note_missing_ir(missing: Option<MissingIr>, args: &Args) -> Result<()> {
+    let note = match missing {
+        // Snapshots are diffed as they are
+        _ if args.snapshots => return Ok(()),
+        None => return Ok(()),
+        Some(MissingIr::Before) => "note: the dump has no `IR Dump Before`, the IR before each pass is reconstructed from the pass before it",
+        Some(MissingIr::After) => "note: the dump has no `IR Dump After`, the IR after each pass is reconstructed from the pass after it",
+    };
+    cli_writeln!(io::stderr(), "{}", note)?;
+    Ok(())
+}
+
+/// Report the malformed pass dumps skipped with `--lenient`, naming the file they
+/// are in if there are several.
+fn warn_skipped(warnings: &[PassDumpError], path: Option<&Path>) -> Result<()> {
+    for warning in warnings {
+        match path {
+            Some(path) => cli_writeln!(
+                io::stderr(),
+                "warning: skipped malformed pass dump in {}: {}",
+                path.display(),
+                warning
+            )?,
+            None => cli_writeln!(
+                io::stderr(),
+                "warning: skipped malformed pass dump: {}",
+                warning
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Point out `--lenient` for errors in malformed pass dumps.
+fn suggest_lenient(report: Report) -> Report {
+    match report
+        .downcast_ref::<PassDumpError>()
+        .and_then(PassDumpError::line)
+    {
+        Some(_) => report.suggestion("Use `--lenient` to skip malformed pass dumps"),
+        None => report,
+    }
+}
+
+/// Print the passes of the function selected with `--function` while the dump is
+/// being read, so that the dump never has to fit in memory.
+fn stream_func(input: impl BufRead, args: &Args) -> Result<()> {
+    let expected = args
+        .function
+        .first()
+        .map(String::as_str)
+        .unwrap_or_default();
+    let options = backend_options(args, args.full_module)?;
+    let mut index = 0;
+    let mut range = PassRange::new(args.from.as_ref(), args.to.as_ref());
+    let mut in_range = 0;
+    // The first and last selected pass, with `--squash`
+    let mut first: Option<(usize, Pass)> = None;
+    let mut last: Option<(usize, Pass)> = None;
+    let bodies = BodyCache::new(args.demangle);
+    let summary = optpipeline::stream_function::<_, color_eyre::Report>(
+        input,
+        &options,
+        |func_name| demangle_text(func_name, args.demangle) == expected,
+        |prefix, missing| {
+            cli_write!(io::stderr(), "{}", prefix)?;
+            note_missing_ir(missing, args)?;
+            enter_pager(args.pager.as_deref());
+            Ok(())
+        },
+        |func_name, pass| {
+            let i = index;
+            index += 1;
+            if !range.contains(i, &pass) {
+                return Ok(());
+            }
+            in_range += 1;
+            if args.squash {
+                if pass_selected(&pass, args, false)? {
+                    match first {
+                        None => first = Some((i, pass)),
+                        Some(_) => last = Some((i, pass)),
+                    }
+                }
+                return Ok(());
+            }
+            if pass_selected(&pass, args, args.skip_unchanged)? {
+                let title = pass_title(&demangle_text(func_name, args.demangle), i, &pass);
+                let (before, after) = diff_sides(&pass);
+                print_diff(&title, before, after, &bodies)?;
+            }
+            if pass.crashed {
+                note_crashed([pass.name.as_str()], args)?;
+            }
+            Ok(())
+        },
+    )
+    .map_err(suggest_lenient)?;
+    warn_skipped(&summary.warnings, None)?;
+
+    if !summary.has_passes {
+        return Err(eyre!(MISSING_DUMP));
+    }
+    if summary.function.is_none() {
+        return Err(eyre!("Function '{}' was not found in the input, use option `--list/-l` to find out all available functions", expected));
+    }
+    if in_range == 0 || range.missing().is_some() {
+        return Err(pass_range_error(range.missing()));
+    }
+    if let (Some((i, first)), Some(func_name)) = (&first, &summary.function) {
+        let last = last.as_ref().map_or((*i, first), |(j, last)| (*j, last));
+        let (i, pass) = squash_passes((*i, first), last);
+        if !(args.skip_unchanged && pass.before == pass.after) {
+            let title = pass_title(&demangle_text(func_name, args.demangle), i, &pass);
+            print_diff(&title, &pass.before, &pass.after, &bodies)?;
+        }
+    }
+    Ok(())
+}
+
+fn backend_options(args: &Args, full_module: bool) -> Result<OptPipelineBackendOptions> {
+    let config = Config::load(args.config.as_deref())?;
+    let rules = config.enabled_rules(&args.rules)?;
+    let rule_patterns = |kind| {
+        rules
+            .iter()
+            .filter(|rule| rule.kind == kind)
+            .map(|rule| rule.pattern().to_string())
+            .collect()
+    };
+
+    Ok(OptPipelineBackendOptions {
+        filter_debug_info: !args.keep_debug_info,
+        filter_ir_metadata: !args.keep_metadata,
+        filter_attributes: !args.keep_attributes,
+        full_module,
+        snapshots: args.snapshots,
+        lenient: args.lenient,
+        line_filters: rule_patterns(RuleKind::Line),
+        inline_filters: rule_patterns(RuleKind::Inline),
+        apply_filters: !args.no_filter,
+        ..Default::default()
+    })
+}
+
 fn matches_pattern(text: &str, pattern: &str, use_regex: bool) -> Result<bool> {
     if use_regex {
         let regex =
@@ -106,57 +515,567 @@ fn matches_pattern(text: &str, pattern: &str, use_regex: bool) -> Result<bool> {
     }
 }
 
-fn demangle_text(text: &str, should_demangle: bool) -> String {
-    if !should_demangle {
-        return text.to_string();
+/// Passes of `pipeline` between `--from` and `--to` that survive the pass filters,
+/// with their 0-based position in the full pipeline.
+fn select_passes<'a>(
+    pipeline: &'a [Pass],
+    args: &Args,
+    skip_unchanged: bool,
+) -> Result<Vec<(usize, &'a Pass)>> {
+    let mut range = PassRange::new(args.from.as_ref(), args.to.as_ref());
+    let mut selected = Vec::new();
+    for (i, pass) in pipeline.iter().enumerate() {
+        if range.contains(i, pass) && pass_selected(pass, args, skip_unchanged)? {
+            selected.push((i, pass));
+        }
     }
+    // Functions the pass range doesn't run in are left out entirely
+    if range.missing().is_some() {
+        selected.clear();
+    }
+    Ok(selected)
+}
 
-    let mut output = Vec::new();
-    let options = demangle::DemangleBuilder::new().build();
-    if demangle::demangle_line(&mut output, text.as_bytes(), options).is_ok() {
-        String::from_utf8_lossy(&output).to_string()
-    } else {
-        text.to_string()
+/// Passes squashed with `--squash`, by function name, with the position of the
+/// first of them.
+type Squashed<'a> = HashMap<&'a str, (usize, Pass)>;
+
+/// With `--squash`, squash the selected passes of each function into one.
+fn squash_functions<'a>(functions: &[(&'a str, &[Pass])], args: &Args) -> Result<Squashed<'a>> {
+    let mut squashed = HashMap::new();
+    if !args.squash {
+        return Ok(squashed);
     }
+    for (func_name, pipeline) in functions {
+        // Unchanged passes don't matter in between, only the end result does
+        let selected = select_passes(pipeline, args, false)?;
+        if let (Some(first), Some(last)) = (selected.first(), selected.last()) {
+            squashed.insert(*func_name, squash_passes(*first, *last));
+        }
+    }
+    Ok(squashed)
+}
+
+/// Squash the passes from `first` to `last` into one, from the IR before `first`
+/// to the IR after `last`, or to the IR `last` was given if the dump ends in it.
+fn squash_passes((i, first): (usize, &Pass), (j, last): (usize, &Pass)) -> (usize, Pass) {
+    let after = match last.crashed {
+        true => last.before.clone(),
+        false => last.after.clone(),
+    };
+    let name = match i == j {
+        true => first.name.clone(),
+        false => format!("{} → {} ({} passes)", first.name, last.name, j - i + 1),
+    };
+    let pass = Pass {
+        name,
+        machine: first.machine,
+        ir_changed: first.before != after,
+        before: first.before.clone(),
+        after,
+        crashed: false,
+        line: first.line,
+    };
+    (i, pass)
 }
 
-fn print_func(
+/// The passes of `func_name` to show: those picked by `select_passes`, or with
+/// `--squash` the one they were squashed into.
+fn shown_passes<'a>(
     func_name: &str,
-    pipeline: &[Pass],
-    skip_unchanged: bool,
-    pass_filter: Option<&str>,
-    use_regex: bool,
-    should_demangle: bool,
+    pipeline: &'a [Pass],
+    squashed: &'a Squashed,
+    args: &Args,
+) -> Result<Vec<(usize, &'a Pass)>> {
+    if !args.squash {
+        return select_passes(pipeline, args, args.skip_unchanged);
+    }
+    Ok(squashed
+        .get(func_name)
+        .filter(|(_, pass)| !(args.skip_unchanged && pass.before == pass.after))
+        .map(|(i, pass)| (*i, pass))
+        .into_iter()
+        .collect())
+}
+
+fn pass_selected(pass: &Pass, args: &Args, skip_unchanged: bool) -> Result<bool> {
+    if args.pass.is_some() || args.pass_exclude.is_some() {
+        let demangled_name = demangle_text(&pass.name, args.demangle);
+        if let Some(filter) = &args.pass {
+            if !matches_pattern(&demangled_name, filter, args.extended_regex)? {
+                return Ok(false);
+            }
+        }
+        if let Some(filter) = &args.pass_exclude {
+            if matches_pattern(&demangled_name, filter, args.extended_regex)? {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(!(skip_unchanged && pass.before == pass.after))
+}
+
+/// Make sure `--from` and `--to` select passes of at least one of `pipelines`.
+fn check_pass_range<'a>(
+    pipelines: impl IntoIterator<Item = &'a [Pass]>,
+    args: &Args,
 ) -> Result<()> {
-    for (i, pass) in pipeline.iter().enumerate() {
-        let demangled_name = demangle_text(&pass.name, should_demangle);
+    if args.from.is_none() && args.to.is_none() {
+        return Ok(());
+    }
+    let mut missing = None;
+    for pipeline in pipelines {
+        let mut range = PassRange::new(args.from.as_ref(), args.to.as_ref());
+        let mut in_range = false;
+        for (i, pass) in pipeline.iter().enumerate() {
+            in_range |= range.contains(i, pass);
+        }
+        match range.missing() {
+            None if in_range => return Ok(()),
+            pipeline_missing => missing = missing.or(pipeline_missing),
+        }
+    }
+    Err(pass_range_error(missing))
+}
 
-        if let Some(filter) = pass_filter {
-            if !matches_pattern(&demangled_name, filter, use_regex)? {
-                continue;
+fn pass_range_error(missing: Option<(&str, &PassSelector)>) -> Report {
+    match missing {
+        Some((option, selector)) => eyre!(
+            "There is no pass '{}' for `{}` in the selected functions",
+            selector,
+            option
+        ),
+        None => eyre!("The pass given to `--to` comes before the one given to `--from`"),
+    }
+    .suggestion("Passes are numbered as in the diff titles, and named without the ` on ...` part")
+}
+
+/// A `--function` pattern: a function name, a glob pattern, or a regex with `-E`.
+enum FunctionPattern {
+    Name(String),
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl FunctionPattern {
+    fn new(pattern: &str, use_regex: bool) -> Result<Self> {
+        if use_regex {
+            let regex = Regex::new(pattern)
+                .wrap_err_with(|| format!("Invalid regex pattern: {}", pattern))?;
+            return Ok(FunctionPattern::Regex(regex));
+        }
+        if !pattern.contains(['*', '?', '[']) {
+            return Ok(FunctionPattern::Name(pattern.to_string()));
+        }
+        // Demangled names like `operator[]` aren't valid globs, take them literally
+        Ok(match glob::Pattern::new(pattern) {
+            Ok(glob) => FunctionPattern::Glob(glob),
+            Err(_) => FunctionPattern::Name(pattern.to_string()),
+        })
+    }
+
+    fn is_match(&self, func_name: &str) -> bool {
+        match self {
+            FunctionPattern::Name(name) => name == func_name,
+            FunctionPattern::Glob(glob) => glob.as_str() == func_name || glob.matches(func_name),
+            FunctionPattern::Regex(regex) => regex.is_match(func_name),
+        }
+    }
+}
+
+fn function_patterns(args: &Args) -> Result<Vec<FunctionPattern>> {
+    args.function
+        .iter()
+        .map(|pattern| FunctionPattern::new(pattern, args.extended_regex))
+        .collect()
+}
+
+/// Functions to show, sorted by name: all of them, or those matching any of the
+/// `--function` patterns. These are matched against the demangled name if
+/// `--demangle` is given.
+fn select_functions<'a>(
+    result: &'a OptPipelineResults,
+    args: &Args,
+) -> Result<Vec<(&'a str, &'a [Pass])>> {
+    let patterns = function_patterns(args)?;
+    let selected: Vec<_> = result
+        .iter()
+        .sorted_by_key(|(func_name, _)| *func_name)
+        .map(|(func_name, pipeline)| (func_name.as_str(), pipeline.as_slice()))
+        .filter(|(func_name, _)| {
+            if patterns.is_empty() {
+                return true;
             }
+            let demangled_name = demangle_text(func_name, args.demangle);
+            patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&demangled_name))
+        })
+        .collect();
+
+    if selected.is_empty() && !patterns.is_empty() {
+        let kind = match args.extended_regex {
+            true => "regex ",
+            false => "",
+        };
+        return Err(eyre!(
+            "No function matching {}{} was found in the input, use option `--list/-l` to find out all available functions",
+            kind,
+            args.function.iter().map(|pattern| format!("'{}'", pattern)).join(", ")
+        ));
+    }
+    Ok(selected)
+}
+
+fn render_diff(title: &str, before: &str, after: &str, bodies: &BodyCache) -> String {
+    // Leave an empty side empty, rather than a single blank line
+    let terminate = |text: &str| match text.is_empty() {
+        true => String::new(),
+        false => format!("{}\n", text),
+    };
+    let demangled_before = terminate(&bodies.demangle(before));
+    let demangled_after = terminate(&bodies.demangle(after));
+
+    let diff = TextDiff::from_lines(&demangled_before, &demangled_after);
+
+    format!(
+        "diff --git a/{title} b/{title}\n--- a/{title}\n+++ b/{title}\n{}\n",
+        diff.unified_diff().context_radius(10)
+    )
+}
+
+fn print_diff(title: &str, before: &str, after: &str, bodies: &BodyCache) -> Result<()> {
+    cli_write!(
+        io::stdout(),
+        "{}",
+        render_diff(title, before, after, bodies)
+    )?;
+    Ok(())
+}
+
+fn pass_title(func_name: &str, i: usize, pass: &Pass) -> String {
+    let crashed = if pass.crashed { " (crashed)" } else { "" };
+    format!("({}·{}) {}{}", i + 1, func_name, &pass.name, crashed)
+}
+
+/// The IR to diff for `pass`. A pass the compiler crashed in has no IR after it,
+/// so the IR it was given is shown in full instead.
+fn diff_sides(pass: &Pass) -> (&str, &str) {
+    match pass.crashed {
+        true => ("", &pass.before),
+        false => (&pass.before, &pass.after),
+    }
+}
+
+/// Point out the passes the dump ends in, the last pass of every pipeline they
+/// are part of.
+fn note_crashed<'a>(passes: impl IntoIterator<Item = &'a str>, args: &Args) -> Result<()> {
+    for pass in passes.into_iter().unique() {
+        cli_writeln!(
+            io::stderr(),
+            "note: the dump ends in the middle of `{}`, the compiler probably crashed in it. The IR it was given is shown in full{}",
+            demangle_text(pass, args.demangle),
+            match args.crash_ir {
+                Some(_) => "",
+                None => ", write it to a file for `opt` with `--crash-ir <FILE>`",
+            }
+        )?;
+    }
+    Ok(())
+}
+
+/// Options to parse the dump with for reproducers. Filters would strip the
+/// declarations, attributes and metadata `opt` needs.
+fn reproducer_options(dump: &[u8], args: &Args) -> Result<OptPipelineBackendOptions> {
+    Ok(OptPipelineBackendOptions {
+        apply_filters: false,
+        ..backend_options(args, args.full_module || optpipeline::is_full_module(dump))?
+    })
+}
+
+/// Write the IR given to the pass the dump ends in to `path`, and suggest how to
+/// run the pass on it again.
+fn write_crash_ir(dump: &[u8], functions: &[&str], path: &Path, args: &Args) -> Result<()> {
+    let options = reproducer_options(dump, args)?;
+    let (_, result, _) = optpipeline::process(dump, &options).wrap_err("Parsing error")?;
+    let pipelines: Vec<&[Pass]> = functions
+        .iter()
+        .filter_map(|func_name| result.get(*func_name))
+        .map(Vec::as_slice)
+        .collect();
+    let (pass, ir) = reproduce::crashed_ir(&pipelines)?;
+    let context = optpipeline::module_at(dump, &options, pass.line)?;
+    let reproducer = reproduce::reproducer(pass, &ir, context.as_deref())?;
+    write_reproducer(&reproducer, "given to", path)?;
+    suggest_opt(&reproducer, "-disable-output", path, None)
+}
+
+/// Write the IR before pass `index` (1-based) of `func_name`, and with
+/// `--extract-after` the IR after it, and suggest how to run the pass on it.
+fn extract_pass(dump: &[u8], func_name: &str, index: usize, args: &Args) -> Result<()> {
+    let options = reproducer_options(dump, args)?;
+    let (_, result, _) = optpipeline::process(dump, &options).wrap_err("Parsing error")?;
+    let pipeline = result.get(func_name).map_or(&[][..], Vec::as_slice);
+    let pass = index
+        .checked_sub(1)
+        .and_then(|i| pipeline.get(i))
+        .ok_or_else(|| {
+            eyre!(
+                "There is no pass {} in '{}', its passes are numbered 1 to {}",
+                index,
+                demangle_text(func_name, args.demangle),
+                pipeline.len()
+            )
+        })?;
+    if args.extract_after && pass.crashed {
+        return Err(eyre!(
+            "The dump ends in the middle of `{}`, there is no IR after it",
+            pass.name
+        ));
+    }
+    let context = optpipeline::module_at(dump, &options, pass.line)?;
+
+    let path = match &args.extract_to {
+        Some(path) => path.clone(),
+        None => PathBuf::from(format!("pass-{index}.ll")),
+    };
+    let reproducer = reproduce::reproducer(pass, &pass.before, context.as_deref())?;
+    write_reproducer(&reproducer, "before", &path)?;
+    let after_path = match args.extract_after {
+        true => {
+            let after_path = path.with_file_name(format!(
+                "{}-after.ll",
+                path.file_stem().unwrap_or_default().to_string_lossy()
+            ));
+            let after = reproduce::reproducer(pass, &pass.after, context.as_deref())?;
+            write_reproducer(&after, "after", &after_path)?;
+            Some(after_path)
         }
+        false => None,
+    };
+    suggest_opt(&reproducer, "-S", &path, after_path.as_deref())
+}
 
-        if skip_unchanged && pass.before == pass.after {
-            continue;
+/// Write the module of `reproducer` to `path`, pointing out anything missing from
+/// it. `relation` says how the IR relates to the pass, like "before".
+fn write_reproducer(reproducer: &Reproducer, relation: &str, path: &Path) -> Result<()> {
+    std::fs::write(path, &reproducer.ir)
+        .wrap_err_with(|| format!("Failed to write file: {}", path.display()))?;
+    let mut stderr = io::stderr();
+    cli_writeln!(
+        stderr,
+        "Wrote the IR {} `{}` to {}",
+        relation,
+        reproducer.pass,
+        path.display()
+    )?;
+    if !reproducer.unresolved.is_empty() {
+        cli_writeln!(
+            stderr,
+            "warning: the dump doesn't define {}, add them to {} or dump the whole module with `-mllvm -print-module-scope`",
+            reproducer.unresolved.join(", "),
+            path.display()
+        )?;
+    }
+    Ok(())
+}
+
+/// Suggest an `opt` command that runs the pass of `reproducer` on `path`.
+fn suggest_opt(
+    reproducer: &Reproducer,
+    flags: &str,
+    path: &Path,
+    after_path: Option<&Path>,
+) -> Result<()> {
+    let mut suggestion = match reproducer.opt_passes() {
+        Some(passes) => format!(
+            "Run the pass again with:\n  opt {} -passes='{}' {}",
+            flags,
+            passes,
+            path.display()
+        ),
+        None => format!(
+            "Run the pass again with `opt {} -passes=<pass> {}`, `opt -print-passes` lists the names of passes",
+            flags,
+            path.display()
+        ),
+    };
+    if let Some(after_path) = after_path {
+        suggestion += &format!("\nand compare its output with {}", after_path.display());
+    }
+    cli_writeln!(io::stderr(), "{}", suggestion)?;
+    Ok(())
+}
+
+/// Print the diffs of `passes` in order. They are rendered in parallel a batch at
+/// a time, so that output starts early and only one batch is held in memory.
+fn print_diffs(passes: &[(String, &Pass)], demangle: Option<DemangleMode>) -> Result<()> {
+    let mut stdout = io::stdout();
+    let bodies = BodyCache::new(demangle);
+    for batch in passes.chunks(rayon::current_num_threads() * 8) {
+        let diffs: Vec<String> = batch
+            .par_iter()
+            .map(|(title, pass)| {
+                let (before, after) = diff_sides(pass);
+                render_diff(title, before, after, &bodies)
+            })
+            .collect();
+        for diff in diffs {
+            cli_write!(stdout, "{}", diff)?;
         }
+    }
+    Ok(())
+}
+
+/// Print how the IR after each pass differs between the `old` and `new` pipelines
+/// of a function, and the passes that only run in one of them.
+fn print_func_comparison(
+    func_name: &str,
+    old: &[(usize, &Pass)],
+    new: &[(usize, &Pass)],
+    args: &Args,
+) -> Result<()> {
+    let bodies = BodyCache::new(args.demangle);
+    for step in compare::align_pipelines(old, new) {
+        match step {
+            AlignedPass::Both {
+                old: (i, old),
+                new: (j, new),
+            } => {
+                if old.after == new.after {
+                    continue;
+                }
+                // Only show where the divergence is introduced or changes
+                if args.skip_unchanged && !old.ir_changed && !new.ir_changed {
+                    continue;
+                }
+                let title = format!("({}→{}·{}) {}", i + 1, j + 1, func_name, old.name);
+                print_diff(&title, &old.after, &new.after, &bodies)?;
+            }
+            AlignedPass::OldOnly((i, pass)) | AlignedPass::NewOnly((i, pass)) => {
+                if args.skip_unchanged && !pass.ir_changed {
+                    continue;
+                }
+                let (title, side) = match step {
+                    AlignedPass::OldOnly(_) => (format!("({}→-·{})", i + 1, func_name), "old"),
+                    _ => (format!("(-→{}·{})", i + 1, func_name), "new"),
+                };
+                let title = format!("{} {} [only in {}]", title, pass.name, side);
+                print_diff(&title, &pass.before, &pass.after, &bodies)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn compare_dumps(old_path: &Path, new_path: &Path, args: &Args) -> Result<()> {
+    let old_dump = read_input(Some(old_path))?;
+    let new_dump = read_input(Some(new_path))?;
+    check_dump(&old_dump)?;
+    check_dump(&new_dump)?;
+
+    let full_module = args.full_module
+        || optpipeline::is_full_module(&old_dump)
+        || optpipeline::is_full_module(&new_dump);
+    let options = backend_options(args, full_module)?;
+    let (_, old_result, old_warnings) = optpipeline::process(&old_dump, &options)
+        .wrap_err_with(|| format!("Parsing error in {}", old_path.display()))
+        .map_err(suggest_lenient)?;
+    let (_, new_result, new_warnings) = optpipeline::process(&new_dump, &options)
+        .wrap_err_with(|| format!("Parsing error in {}", new_path.display()))
+        .map_err(suggest_lenient)?;
+    warn_skipped(&old_warnings, Some(old_path))?;
+    warn_skipped(&new_warnings, Some(new_path))?;
+
+    let (old_functions, new_functions) = match (
+        select_functions(&old_result, args),
+        select_functions(&new_result, args),
+    ) {
+        (Err(err), Err(_)) => return Err(err),
+        (old, new) => (old.unwrap_or_default(), new.unwrap_or_default()),
+    };
+    check_pass_range(
+        old_functions
+            .iter()
+            .chain(&new_functions)
+            .map(|(_, pipeline)| *pipeline),
+        args,
+    )?;
 
-        let demangled_before = demangle_text(&pass.before, should_demangle) + "\n";
-        let demangled_after = demangle_text(&pass.after, should_demangle) + "\n";
+    type Pipelines<'a> = (Option<&'a [Pass]>, Option<&'a [Pass]>);
+    let mut functions: BTreeMap<&str, Pipelines> = BTreeMap::new();
+    for (func_name, pipeline) in old_functions {
+        functions.entry(func_name).or_default().0 = Some(pipeline);
+    }
+    for (func_name, pipeline) in new_functions {
+        functions.entry(func_name).or_default().1 = Some(pipeline);
+    }
 
-        let diff = TextDiff::from_lines(&demangled_before, &demangled_after);
+    for (func_name, pipelines) in &functions {
+        let path = match pipelines {
+            (Some(_), None) => old_path,
+            (None, Some(_)) => new_path,
+            _ => continue,
+        };
+        cli_writeln!(
+            io::stderr(),
+            "Function '{}' only appears in {}",
+            demangle_text(func_name, args.demangle),
+            path.display()
+        )?;
+    }
 
-        let title = format!("({}·{}) {}", i + 1, func_name, &pass.name);
-        let mut stdout = io::stdout();
-        cli_writeln!(stdout, "diff --git a/{} b/{}", title, title)?;
-        cli_writeln!(stdout, "--- a/{}", title)?;
-        cli_writeln!(stdout, "+++ b/{}", title)?;
-        cli_writeln!(stdout, "{}", diff.unified_diff().context_radius(10))?;
+    enter_pager(args.pager.as_deref());
+    for (func_name, pipelines) in functions {
+        let (Some(old), Some(new)) = pipelines else {
+            continue;
+        };
+        let old = select_passes(old, args, false)?;
+        let new = select_passes(new, args, false)?;
+        let func_name = demangle_text(func_name, args.demangle);
+        print_func_comparison(&func_name, &old, &new, args)?;
     }
 
     Ok(())
 }
 
+fn write_json(
+    prefix: &str,
+    functions: &[(&str, &[Pass])],
+    squashed: &Squashed,
+    args: &Args,
+) -> Result<()> {
+    let mut report = json::Report {
+        version: json::SCHEMA_VERSION,
+        prefix,
+        functions: Vec::new(),
+    };
+    let bodies = BodyCache::new(args.demangle);
+    for (func_name, pipeline) in functions {
+        let passes = shown_passes(func_name, pipeline, squashed, args)?
+            .into_iter()
+            .map(|(i, pass)| json::Pass {
+                index: i + 1,
+                name: demangle_text(&pass.name, args.demangle),
+                machine: pass.machine,
+                before: bodies.demangle(&pass.before).to_string(),
+                after: bodies.demangle(&pass.after).to_string(),
+                ir_changed: pass.ir_changed,
+                crashed: pass.crashed,
+            })
+            .collect();
+        report.functions.push(json::Function {
+            name: demangle_text(func_name, args.demangle),
+            passes,
+        });
+    }
+
+    let json = serde_json::to_string_pretty(&report).wrap_err("Failed to serialize JSON")?;
+    cli_writeln!(io::stdout(), "{}", json)?;
+    Ok(())
+}
+
 fn auto_select_pager() -> Option<&'static str> {
     if which::which("delta").is_ok() {
         Some("delta")
@@ -186,35 +1105,33 @@ fn enter_pager(pager: Option<&str>) {
 #[cfg(not(unix))]
 fn enter_pager(_pager: Option<&str>) {}
 
-fn list_functions(dump: &str, should_demangle: bool) -> HashSet<String> {
+fn list_functions(dump: &[u8], demangle: Option<DemangleMode>) -> HashSet<String> {
     let mut functions = HashSet::new();
-    let haystack = dump.as_bytes();
+    let haystack = dump;
     {
         let it = memmem::find_iter(haystack, b"define ");
         for start in it {
-            let start = start + memchr::memchr(b'@', &haystack[start..]).unwrap() + 1;
-            let end = memchr::memchr(b'(', &haystack[start..]).unwrap();
-            let name = &dump[start..start + end];
-            let demangled = if should_demangle {
-                demangle_text(name, true)
-            } else {
-                name.to_string()
+            // Skip anything malformed, like a dump cut off in the middle of a line
+            let Some(at) = memchr::memchr(b'@', &haystack[start..]) else {
+                continue;
+            };
+            let start = start + at + 1;
+            let Some(end) = memchr::memchr(b'(', &haystack[start..]) else {
+                continue;
             };
-            functions.insert(demangled);
+            let name = String::from_utf8_lossy(&dump[start..start + end]);
+            functions.insert(demangle_text(&name, demangle));
         }
     }
     {
         let it = memmem::find_iter(haystack, b"# Machine code for function ");
         for start in it {
             let start = start + b"# Machine code for function ".len();
-            let end = memchr::memchr(b':', &haystack[start..]).unwrap();
-            let name = &dump[start..start + end];
-            let demangled = if should_demangle {
-                demangle_text(name, true)
-            } else {
-                name.to_string()
+            let Some(end) = memchr::memchr(b':', &haystack[start..]) else {
+                continue;
             };
-            functions.insert(demangled);
+            let name = String::from_utf8_lossy(&dump[start..start + end]);
+            functions.insert(demangle_text(&name, demangle));
         }
     }
     functions
@@ -224,73 +1141,182 @@ fn main() -> Result<()> {
     color_eyre::install()?;
 
     let args = Args::parse();
-    let dump = read_input(&args).wrap_err_with(|| match &args.input {
-        None => "Failed to read from stdin".to_string(),
-        Some(path) => format!("Failed to read from file: {}", path.display()),
-    })?;
 
-    if !dump.contains("IR Dump Before") {
-        return Err(eyre!("Did you forget to add `-mllvm -print-before-all`?"));
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .wrap_err("Failed to start worker threads")?;
     }
 
-    if !dump.contains("IR Dump After") {
-        return Err(eyre!("Did you forget to add `-mllvm -print-after-all`?"));
+    if let Some(paths) = &args.compare {
+        return compare_dumps(&paths[0], &paths[1], &args);
     }
 
+    // A single function can be diffed without reading the whole dump into memory
+    let patterns = function_patterns(&args)?;
+    let streaming = matches!(patterns[..], [FunctionPattern::Name(_)])
+        && !args.list
+        && !args.tui
+        && args.html.is_none()
+        && args.crash_ir.is_none()
+        && args.extract.is_none()
+        && args.output == OutputFormat::Diff;
+    if streaming {
+        return if args.command.is_empty() {
+            stream_func(open_input(args.input.as_deref())?, &args)
+        } else {
+            stream_func(run_compiler(&args)?.as_bytes(), &args)
+        };
+    }
+
+    let dump = if args.command.is_empty() {
+        read_input(args.input.as_deref())?
+    } else {
+        DumpText::Owned(run_compiler(&args)?)
+    };
+    check_dump(&dump)?;
+
+    let full_module = args.full_module || optpipeline::is_full_module(&dump);
+
     if args.list {
-        for func in list_functions(&dump, args.demangle).into_iter().sorted() {
+        let mut functions = if optpipeline::is_interleaved(&dump) {
+            // Functions of several compilers are only told apart by parsing the dump
+            let options = backend_options(&args, full_module)?;
+            let (_, result, warnings) = optpipeline::process(&dump, &options)
+                .wrap_err("Parsing error")
+                .map_err(suggest_lenient)?;
+            warn_skipped(&warnings, None)?;
+            result
+                .into_keys()
+                .map(|name| demangle_text(&name, args.demangle))
+                .collect()
+        } else {
+            list_functions(&dump, args.demangle)
+        };
+        if full_module {
+            functions.insert(optpipeline::FULL_MODULE.to_string());
+        }
+        if !patterns.is_empty() {
+            functions.retain(|func| patterns.iter().any(|pattern| pattern.is_match(func)));
+        }
+        for func in functions.into_iter().sorted() {
             cli_writeln!(io::stdout(), "{func}")?;
         }
         return Ok(());
     }
 
-    let (prefix, result) = optpipeline::process(&dump, true).wrap_err("Parsing error")?;
-    cli_write!(io::stderr(), "{}", prefix)?;
+    let options = backend_options(&args, full_module)?;
+    let (prefix, result, warnings) = optpipeline::process(&dump, &options)
+        .wrap_err("Parsing error")
+        .map_err(suggest_lenient)?;
+    warn_skipped(&warnings, None)?;
+    let functions = select_functions(&result, &args)?;
 
-    if let Some(expected) = args.function {
-        let (func_name, pipeline) = if args.extended_regex {
-            let regex = Regex::new(&expected)
-                .wrap_err_with(|| format!("Invalid regex pattern: {}", expected))?;
-            result
-                .iter()
-                .map(|(func_name, pipeline)| (demangle_text(func_name, args.demangle), pipeline))
-                .find(|(func_name,_)| regex.is_match(func_name))
-                .ok_or_else(|| {
-                    eyre!(
-                        "No function matching regex '{}' was found in the input, use option `--list/-l` to find out all available functions",
-                        expected
-                    )
-                })?
-        } else {
-            result
-                .iter()
-                .map(|(func_name, pipeline)| (demangle_text(func_name, args.demangle), pipeline))
-                .find(|(func_name,_)| func_name == &expected)
-                .ok_or_else(|| eyre!("Function '{}' was not found in the input, use option `--list/-l` to find out all available functions", expected))?
+    if let Some(index) = args.extract {
+        let [(func_name, _)] = functions[..] else {
+            return Err(eyre!(
+                "`--extract` needs a single function, select it with `--function/-f`"
+            ));
         };
+        return extract_pass(&dump, func_name, index, &args);
+    }
+    check_pass_range(functions.iter().map(|(_, pipeline)| *pipeline), &args)?;
+    let squashed = squash_functions(&functions, &args)?;
 
-        enter_pager(args.pager.as_deref());
-        print_func(
-            &func_name,
-            pipeline,
-            args.skip_unchanged,
-            args.pass.as_deref(),
-            args.extended_regex,
-            args.demangle,
-        )?;
-    } else {
-        enter_pager(args.pager.as_deref());
-        for (func, pipeline) in result.iter().sorted_by_key(|(func, _)| *func) {
-            print_func(
-                func,
-                pipeline,
-                args.skip_unchanged,
-                args.pass.as_deref(),
-                args.extended_regex,
-                args.demangle,
+    if let Some(path) = &args.crash_ir {
+        let func_names: Vec<&str> = functions.iter().map(|(func_name, _)| *func_name).collect();
+        write_crash_ir(&dump, &func_names, path, &args)?;
+    }
+
+    if args.tui {
+        let mut selected = Vec::new();
+        for (name, pipeline) in functions {
+            // Unchanged passes are hidden interactively instead
+            let passes = select_passes(pipeline, &args, false)?;
+            selected.push(tui::Function { name, passes });
+        }
+        tui::run(selected, args.skip_unchanged, args.demangle)
+            .wrap_err("Failed to run the terminal UI")?;
+        return Ok(());
+    }
+
+    if let Some(path) = &args.html {
+        let mut selected = Vec::new();
+        for (func_name, pipeline) in &functions {
+            selected.push((
+                demangle_text(func_name, args.demangle),
+                shown_passes(func_name, pipeline, &squashed, &args)?,
+            ));
+        }
+        let title = match &args.input {
+            Some(input) => input.display().to_string(),
+            None => "<stdin>".to_string(),
+        };
+        let file = std::fs::File::create(path)
+            .wrap_err_with(|| format!("Failed to create file: {}", path.display()))?;
+        let mut out = io::BufWriter::new(file);
+        html::write_report(&mut out, &title, &prefix, &selected, args.demangle)
+            .and_then(|_| out.flush())
+            .wrap_err_with(|| format!("Failed to write HTML report: {}", path.display()))?;
+        return Ok(());
+    }
+
+    match args.output {
+        OutputFormat::Diff => {
+            cli_write!(io::stderr(), "{}", prefix)?;
+            note_missing_ir(optpipeline::missing_ir(&dump), &args)?;
+            let mut diffs = Vec::new();
+            for (func_name, pipeline) in &functions {
+                let passes = shown_passes(func_name, pipeline, &squashed, &args)?;
+                let func_name = demangle_text(func_name, args.demangle);
+                for (i, pass) in passes {
+                    diffs.push((pass_title(&func_name, i, pass), pass));
+                }
+            }
+            note_crashed(
+                diffs
+                    .iter()
+                    .filter(|(_, pass)| pass.crashed)
+                    .map(|(_, pass)| pass.name.as_str()),
+                &args,
             )?;
+            enter_pager(args.pager.as_deref());
+            print_diffs(&diffs, args.demangle)?;
         }
+        OutputFormat::Json => write_json(&prefix, &functions, &squashed, &args)?,
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "tests/fixtures/print-before-after.txt";
+
+    #[test]
+    fn read_input_maps_files() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(DUMP);
+        let text = read_input(Some(&path)).unwrap();
+        assert!(matches!(text, DumpText::Mapped(_)));
+        assert_eq!(&*text, std::fs::read(&path).unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_input_reads_pipes() {
+        use std::os::fd::AsRawFd;
+
+        let dump = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join(DUMP)).unwrap();
+        let (reader, mut writer) = io::pipe().unwrap();
+        let written = dump.clone();
+        let writing = std::thread::spawn(move || writer.write_all(&written));
+        let path = PathBuf::from(format!("/dev/fd/{}", reader.as_raw_fd()));
+        let text = read_input(Some(&path)).unwrap();
+        writing.join().unwrap().unwrap();
+        assert!(matches!(text, DumpText::Owned(_)));
+        assert_eq!(&*text, dump);
+    }
+}
@@ -1,45 +1,117 @@
 use itertools::Itertools;
-use memchr::memchr_iter;
+use memchr::{memchr, memmem};
 use regex::Regex;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Debug)]
 pub struct Pass {
     pub name: String,
     pub machine: bool,
-    pub after: String,
-    pub before: String,
+    pub after: Arc<str>,
+    pub before: Arc<str>,
     pub ir_changed: bool,
+    /// The dump ends between the `IR Dump Before` and `IR Dump After` of this pass,
+    /// e.g. because the compiler crashed in it. `after` is empty
+    pub crashed: bool,
+    /// Line of the pass's first dump in the input
+    pub line: usize,
 }
 
-type OptPipelineResults = HashMap<String, Vec<Pass>>;
+pub type OptPipelineResults = HashMap<String, Vec<Pass>>;
+
+/// The name module passes are shown under in `-print-module-scope` dumps
+pub const FULL_MODULE: &str = "<Full Module>";
 
 #[allow(dead_code)]
 #[derive(Debug)]
-struct OptPipelineBackendOptions {
-    filter_debug_info: bool,
-    filter_ir_metadata: bool,
-    full_module: bool,
-    no_discard_value_names: bool,
-    demangle: bool,
-    library_functions: bool,
-    apply_filters: bool,
+pub struct OptPipelineBackendOptions {
+    pub filter_debug_info: bool,
+    pub filter_ir_metadata: bool,
+    /// Strip `; Function Attrs:`, `attributes #N`, `declare` lines and `#N` references
+    pub filter_attributes: bool,
+    pub full_module: bool,
+    /// Diff every pass dump against the one before it, for dumps of a few selected
+    /// passes where neighbouring dumps aren't of the same pass
+    pub snapshots: bool,
+    /// Skip malformed pass dumps with a warning instead of failing
+    pub lenient: bool,
+    /// Extra user-supplied regexes removing every line they match in
+    pub line_filters: Vec<String>,
+    /// Extra user-supplied regexes removing matches within a line
+    pub inline_filters: Vec<String>,
+    pub no_discard_value_names: bool,
+    pub demangle: bool,
+    pub library_functions: bool,
+    pub apply_filters: bool,
 }
 
-#[derive(Debug)]
+impl Default for OptPipelineBackendOptions {
+    fn default() -> Self {
+        Self {
+            filter_debug_info: true,
+            filter_ir_metadata: true,
+            filter_attributes: true,
+            full_module: false,
+            snapshots: false,
+            lenient: false,
+            line_filters: Vec::new(),
+            inline_filters: Vec::new(),
+            no_discard_value_names: false,
+            demangle: false,
+            library_functions: false,
+            apply_filters: true,
+        }
+    }
+}
+
+/// What a pass dump header says about the IR that follows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DumpKind {
+    Before,
+    After,
+    /// `-print-changed`: the IR before the first pass
+    Start,
+    /// `-print-changed`: the pass didn't change the IR, so it wasn't printed
+    Unchanged,
+    /// `-print-changed`: the pass deleted the function
+    Deleted,
+    /// `-print-changed`: pass managers, adaptors and passes excluded by a filter
+    Ignored,
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
 struct PassDump {
     header: String,
+    kind: DumpKind,
     affected_function: Option<String>,
+    /// Index of the module the dump belongs to, see [`ModuleTracker`]
+    module: usize,
+    /// Line of the header in the input
+    line: usize,
     machine: bool,
-    lines: String,
+    /// Shared between all functions when the dump prints the full module
+    lines: Arc<str>,
+    /// The IR before the pass, for `-print-changed=diff` dumps where `lines` only
+    /// has the IR after it
+    before: Option<Arc<str>>,
 }
 
 #[derive(Debug)]
 struct SplitPassDump {
     header: String,
+    kind: DumpKind,
+    module: usize,
+    line: usize,
     machine: bool,
-    functions: HashMap<String, Vec<String>>,
+    lines: Arc<str>,
+    /// Byte range of each function within `lines`
+    functions: HashMap<String, Range<usize>>,
 }
 
 pub struct LlvmPassDumpParser {
@@ -54,18 +126,171 @@ pub struct LlvmPassDumpParser {
 #[derive(Debug, Error)]
 pub enum PassDumpError {
     #[error(
-        "Consecutive pass headers in dump file do not match:\n\
+        "Consecutive pass headers in dump file do not match at line {line}:\n\
         First:  '{before_header}'\n\
         Second: '{after_header}'\n\n\
         'optdiff' compares each pass dump with its immediate next dump in the file.\n\
         This error typically occurs when multiple compiler instances write to the same dump file.\n\
-        Please run a single compiler instance at a time."
+        Tag the lines of each compiler with its process ID to tell them apart, e.g. by piping\n\
+        its output through `sed -u \"s/^/[$$] /\"`."
     )]
     PassMismatch {
+        line: usize,
         before_header: String,
         after_header: String,
     },
+    #[error("Malformed pass dump header at line {line}: '{snippet}'")]
+    MalformedHeader { line: usize, snippet: String },
+    #[error("Unexpected pass dump header at line {line}: '{snippet}'")]
+    UnexpectedHeader { line: usize, snippet: String },
+    #[error("Malformed function in the pass dump at line {line}: '{snippet}'")]
+    MalformedFunction { line: usize, snippet: String },
+    #[error(
+        "The loop pass dump at line {line} doesn't follow a pass dump of its function: '{snippet}'"
+    )]
+    OrphanLoop { line: usize, snippet: String },
+    #[error(
+        "The pass dumps before and after '{snippet}' at line {line} mix LLVM IR and Machine IR"
+    )]
+    MixedIr { line: usize, snippet: String },
+    #[error(
+        "Can't tell which functions the diff at line {line} is of without the \
+        `IR Dump At Start` of its module: '{snippet}'"
+    )]
+    UnattributedDiff { line: usize, snippet: String },
+    #[error("Failed to build IR filters: {0}")]
+    InvalidFilter(#[from] regex::Error),
+    #[error("The pass dump is not valid UTF-8 at line {line}")]
+    InvalidUtf8 { line: usize },
+    #[error("Failed to read the pass dump")]
+    Io(#[from] io::Error),
+}
+
+impl PassDumpError {
+    /// Line of the input the error is at, for errors in malformed pass dumps.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            PassDumpError::PassMismatch { line, .. }
+            | PassDumpError::MalformedHeader { line, .. }
+            | PassDumpError::UnexpectedHeader { line, .. }
+            | PassDumpError::MalformedFunction { line, .. }
+            | PassDumpError::OrphanLoop { line, .. }
+            | PassDumpError::MixedIr { line, .. }
+            | PassDumpError::UnattributedDiff { line, .. } => Some(*line),
+            PassDumpError::InvalidFilter(_)
+            | PassDumpError::InvalidUtf8 { .. }
+            | PassDumpError::Io(_) => None,
+        }
+    }
+}
+
+/// Errors in malformed pass dumps, which are skipped with a warning in lenient mode.
+#[derive(Default)]
+struct Diagnostics {
+    lenient: bool,
+    warnings: Vec<PassDumpError>,
+}
+
+impl Diagnostics {
+    /// Pass on `result`, or in lenient mode keep its error as a warning if it is
+    /// about a malformed pass dump, so that the dump is skipped.
+    fn recover(&mut self, result: Result<(), PassDumpError>) -> Result<(), PassDumpError> {
+        match result {
+            Err(error) if self.lenient && error.line().is_some() => {
+                // Module passes go wrong the same way for every function
+                let message = error.to_string();
+                if self
+                    .warnings
+                    .last()
+                    .is_none_or(|last| last.to_string() != message)
+                {
+                    self.warnings.push(error);
+                }
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod lenient_tests {
+    use super::tests::{pass_irs, pass_names, process_ok};
+    use super::*;
+
+    #[test]
+    fn malformed_function_reports_its_line() {
+        let dump = "*** IR Dump After InstCombinePass on [module] ***\n\
+            ; ModuleID = 'sum.ll'\n\
+            \n\
+            ; Function Attrs: nounwind\n\
+            define i32 @sum\n\
+            }\n";
+        let error = process(dump.as_bytes(), &Default::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            PassDumpError::MalformedFunction { line: 5, ref snippet } if snippet == "define i32 @sum"
+        ));
+    }
+
+    #[test]
+    fn lenient_mode_skips_malformed_dumps() {
+        let dump = include_str!("../tests/fixtures/print-before-after.txt").replace(
+            "*** IR Dump After InstCombinePass on cube ***",
+            "*** IR Dump After SROAPass on cube ***",
+        );
+        let error = process(dump.as_bytes(), &Default::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            PassDumpError::PassMismatch { line: 212, .. }
+        ));
+
+        let options = OptPipelineBackendOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let (_, result, warnings) = process(dump.as_bytes(), &options).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line(), Some(212));
+        let full = process_ok(
+            include_bytes!("../tests/fixtures/print-before-after.txt"),
+            &Default::default(),
+        );
+        assert_eq!(pass_irs(&result["sum"]), pass_irs(&full["sum"]));
+        assert!(!pass_names(&result["cube"]).contains(&"SROAPass on cube"));
+    }
+
+    #[test]
+    fn truncated_dump() {
+        let dump = include_str!("../tests/fixtures/print-before-after.txt");
+        let header = "*** IR Dump After InstCombinePass on sum ***\n";
+        let end = dump.find(header).unwrap() + header.len();
+        // Cut short in the middle of the function
+        let dump = &dump[..dump[end..].find("loop:").unwrap() + end];
+        let result = process_ok(dump.as_bytes(), &Default::default());
+        let last = result["sum"].last().unwrap();
+        assert_eq!(last.name, "InstCombinePass on sum");
+        assert!(!last.crashed);
+        assert!(last.after.starts_with("define i32 @sum("));
+        assert!(!last.after.contains("loop:"));
+
+        // Cut short in the middle of a header
+        let dump = &dump[..dump.find(header).unwrap() + 20];
+        let error = process(dump.as_bytes(), &Default::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            PassDumpError::MalformedHeader { line: 238, .. }
+        ));
+        let options = OptPipelineBackendOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let (_, result, warnings) = process(dump.as_bytes(), &options).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(result["sum"].last().unwrap().name, "InstCombinePass on sum");
+    }
 }
+
 impl LlvmPassDumpParser {
     fn new() -> Self {
         LlvmPassDumpParser {
@@ -83,122 +308,118 @@ impl LlvmPassDumpParser {
         }
     }
 
-    fn breakdown_output_into_pass_dumps(&self, ir: &str) -> Vec<PassDump> {
-        let mut raw_passes = Vec::new();
-        let mut pass: Option<PassDump> = None;
-        let mut last_was_blank = false;
-
-        for line in ir.lines() {
-            let is_header = line.starts_with("; *** ")
-                || line.starts_with("*** ")
-                || line.starts_with("// -----// ")
-                || line.starts_with("# *** ");
-
-            if is_header {
-                if let Some(current_pass) = pass.take() {
-                    raw_passes.push(current_pass);
-                }
-                let header_prefix = if line.starts_with(';') || line.starts_with("#") {
-                    "; *** "
-                } else if line.starts_with("//") {
-                    "// -----// "
-                } else {
-                    "*** "
-                };
-                let header_suffix = if line.starts_with("//") {
-                    " //----- //"
-                } else {
-                    " ***"
-                };
-                let header = &line[header_prefix.len()..];
-                let header = &header[..header.find(header_suffix).unwrap()];
-
-                let affected_function =
-                    if let Some(idx) = line.find("(function: ").or(line.find("(loop: ")) {
-                        let content = &line[idx + 1..];
-                        Some(
-                            content[content.find(' ').unwrap() + 1..content.find(')').unwrap()]
-                                .to_string(),
-                        )
-                    } else {
-                        None
-                    };
+    /// Build a pass dump from its header line at `line_number` and its (unfiltered)
+    /// body, using `scratch` to avoid allocating for anything but the final body.
+    fn parse_pass_dump(
+        &self,
+        line: &str,
+        line_number: usize,
+        body: &str,
+        filter: Option<&Regex>,
+        scratch: &mut String,
+    ) -> Result<PassDump, PassDumpError> {
+        let malformed = || PassDumpError::MalformedHeader {
+            line: line_number,
+            snippet: line.to_string(),
+        };
+        let header_prefix = if line.starts_with(';') || line.starts_with("#") {
+            "; *** "
+        } else if line.starts_with("//") {
+            "// -----// "
+        } else {
+            "*** "
+        };
+        let header_suffix = if line.starts_with("//") {
+            " //----- //"
+        } else {
+            " ***"
+        };
+        let header = &line[header_prefix.len()..];
+        let header = &header[..header.find(header_suffix).ok_or_else(malformed)?];
+        let (header, kind) = dump_kind(header);
+        let affected_function = if let Some(idx) = line.find("(function: ").or(line.find("(loop: "))
+        {
+            let content = &line[idx + 1..];
+            let (_, content) = content.split_once(' ').ok_or_else(malformed)?;
+            let (function, _) = content.split_once(')').ok_or_else(malformed)?;
+            Some(function.to_string())
+        } else {
+            affected_function_from_header(&header)
+        };
 
-                pass = Some(PassDump {
-                    header: header.to_string(),
-                    affected_function,
-                    machine: line.starts_with("#"),
-                    lines: String::new(),
-                });
+        let (before, lines) = match split_changed_diff(body) {
+            Some((before, after)) => (
+                Some(clean_body(&before, filter, scratch)),
+                clean_body(&after, filter, scratch),
+            ),
+            None => (None, clean_body(body, filter, scratch)),
+        };
 
-                last_was_blank = true;
-            } else if let Some(ref mut current_pass) = pass {
-                if line.trim().is_empty() {
-                    if !last_was_blank {
-                        current_pass.lines += line;
-                        current_pass.lines += "\n";
-                    }
-                    last_was_blank = true;
-                } else {
-                    current_pass.lines += line;
-                    current_pass.lines += "\n";
-                    last_was_blank = false;
-                }
-            }
-        }
-        if let Some(current_pass) = pass {
-            raw_passes.push(current_pass);
-        }
-        raw_passes
+        Ok(PassDump {
+            header,
+            kind,
+            affected_function,
+            module: 0,
+            line: line_number,
+            machine: line.starts_with("#"),
+            lines,
+            before,
+        })
     }
 
-    fn breakdown_pass_dumps_into_functions(&self, dump: PassDump) -> SplitPassDump {
+    fn breakdown_pass_dumps_into_functions(
+        &self,
+        dump: PassDump,
+    ) -> Result<SplitPassDump, PassDumpError> {
         let mut pass = SplitPassDump {
-            header: dump.header,
+            header: dump.header.clone(),
+            kind: dump.kind,
+            module: dump.module,
+            line: dump.line,
             machine: dump.machine,
+            lines: dump.lines.clone(),
             functions: HashMap::new(),
         };
-        let mut func: Option<(String, Vec<String>)> = None;
+        let mut func: Option<(String, Range<usize>)> = None;
         let mut is_machine_function_open = false;
+        // Start of the `; Function Attrs:` comment of the next function
+        let mut attributes_start = None;
 
-        for line in dump.lines.lines() {
-            let line = line.to_string();
-            let is_ir_fn = line.starts_with("define ")
-                || line.trim().starts_with("func.func ")
-                || line.trim().starts_with("tt.func");
-            let is_machine_fn = line.starts_with("# Machine code for function ");
+        let mut offset = 0;
+        for (i, line) in dump.lines.lines().enumerate() {
+            let range = offset..offset + line.len();
+            offset = range.end + 1;
+            let attributes = attributes_start.take();
 
-            if is_ir_fn {
-                if func.is_some() {
-                    let (name, lines) = func.take().unwrap();
+            if let Some(name) = function_start(line) {
+                // Names are checked with their line as the dump is read, so only
+                // filters break them here
+                let name = name.ok_or_else(|| PassDumpError::MalformedFunction {
+                    line: dump.line + 1 + i,
+                    snippet: line.to_string(),
+                })?;
+                if let Some((name, lines)) = func.take() {
                     pass.functions.insert(name, lines);
                 }
-                let name = &line[line.find('@').unwrap() + 1..];
-                let name = &name[..name.find('(').unwrap()];
-                func = Some((name.to_string(), vec![line]));
-
-                is_machine_function_open = false;
-            } else if is_machine_fn {
-                if func.is_some() {
-                    let (name, lines) = func.take().unwrap();
-                    pass.functions.insert(name, lines);
-                }
-                let name = &line["# Machine code for function ".len()..line.find(':').unwrap()];
-                func = Some((name.to_string(), vec![line]));
-                is_machine_function_open = true;
+                is_machine_function_open = line.starts_with(MACHINE_FUNCTION);
+                let start = match is_machine_function_open {
+                    true => range.start,
+                    false => attributes.unwrap_or(range.start),
+                };
+                func = Some((name.to_string(), start..range.end));
             } else if line.starts_with("; Preheader:") {
                 if func.is_none() {
-                    func = Some(("<loop>".to_string(), vec![line]));
+                    func = Some(("<loop>".to_string(), range));
                 }
+            } else if line.starts_with("; Function Attrs: ") && func.is_none() {
+                attributes_start = Some(range.start);
             } else if let Some((ref mut name, ref mut lines)) = func {
+                lines.end = range.end;
                 if (!is_machine_function_open && self.function_end.is_match(line.trim()))
                     || (is_machine_function_open && self.machine_function_end.is_match(line.trim()))
                 {
-                    lines.push(line);
                     pass.functions.insert(name.clone(), lines.clone());
                     func = None;
-                } else {
-                    lines.push(line);
                 }
             }
         }
@@ -207,41 +428,151 @@ impl LlvmPassDumpParser {
             pass.functions.insert(name, lines);
         }
 
-        pass
+        Ok(pass)
     }
 
-    fn breakdown_into_pass_dumps_by_function(
+    /// Split `dump` into the functions it prints and hand each of them to `sink`.
+    /// Loops are attributed to the function printed before them.
+    fn split_pass_dump<E: From<PassDumpError>>(
         &self,
-        pass_dumps: Vec<SplitPassDump>,
-    ) -> HashMap<String, Vec<PassDump>> {
-        let mut pass_dumps_by_function = HashMap::new();
-        let mut previous_function: Option<String> = None;
-
-        for pass in pass_dumps {
-            for (function_name, lines) in pass.functions {
-                let name = if function_name == "<loop>" {
-                    previous_function.clone().unwrap()
-                } else {
-                    function_name.clone()
-                };
-                if !pass_dumps_by_function.contains_key(&name) {
-                    pass_dumps_by_function.insert(name.clone(), Vec::new());
-                }
-                pass_dumps_by_function
-                    .get_mut(&name)
-                    .unwrap()
-                    .push(PassDump {
-                        header: pass.header.clone(),
-                        affected_function: None,
-                        machine: pass.machine,
-                        lines: lines.join("\n"),
-                    });
-                if function_name != "<loop>" {
-                    previous_function = Some(name);
+        dump: PassDump,
+        state: &mut SplitState,
+        mut sink: impl FnMut(Target, PassDump) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if dump.kind == DumpKind::Start {
+            let functions = dump
+                .lines
+                .lines()
+                .filter_map(|line| function_start(line).flatten());
+            state.defined_functions = Some(functions.map(|name| Some(name.to_string())).collect());
+        }
+        // `-print-changed` prints nothing for unchanged and deleted functions, and
+        // leaves out the define line in diffs, so go by the header instead, or by
+        // the order of the functions for passes that may change several of them
+        if let Some(before) = &dump.before {
+            let sections: Vec<_> = diff_sections(before)
+                .into_iter()
+                .zip(diff_sections(&dump.lines))
+                .collect();
+            if sections.is_empty() {
+                return self.split_by_header(&dump, &mut state.previous_function, &mut sink);
+            }
+            let functions = state.diff_section_functions(&sections).ok_or_else(|| {
+                PassDumpError::UnattributedDiff {
+                    line: dump.line,
+                    snippet: dump.header.clone(),
                 }
+            })?;
+            for ((before, after), name) in sections.into_iter().zip(functions) {
+                // Functions added by the pass can't be told apart, their define
+                // line isn't printed
+                let Some(name) = name else {
+                    continue;
+                };
+                let section = match after.trim().is_empty() {
+                    true => PassDump {
+                        kind: DumpKind::Deleted,
+                        lines: Arc::default(),
+                        before: None,
+                        ..dump.clone()
+                    },
+                    false => PassDump {
+                        lines: Arc::from(after.as_str()),
+                        before: Some(Arc::from(before.as_str())),
+                        ..dump.clone()
+                    },
+                };
+                sink(Target::Function(name.clone()), section)?;
+                state.previous_function = Some(name);
             }
+            return Ok(());
+        }
+        if matches!(dump.kind, DumpKind::Unchanged | DumpKind::Deleted) && dump.lines.is_empty() {
+            return self.split_by_header(&dump, &mut state.previous_function, &mut sink);
+        }
+
+        let pass = self.breakdown_pass_dumps_into_functions(dump)?;
+        for (function_name, lines) in pass.functions {
+            let name = if function_name == "<loop>" {
+                state
+                    .previous_function
+                    .clone()
+                    .ok_or_else(|| PassDumpError::OrphanLoop {
+                        line: pass.line,
+                        snippet: pass.header.clone(),
+                    })?
+            } else {
+                function_name.clone()
+            };
+            sink(
+                Target::Function(name.clone()),
+                PassDump {
+                    header: pass.header.clone(),
+                    kind: pass.kind,
+                    affected_function: None,
+                    module: pass.module,
+                    line: pass.line,
+                    machine: pass.machine,
+                    lines: Arc::from(&pass.lines[lines]),
+                    before: None,
+                },
+            )?;
+            if function_name != "<loop>" {
+                state.previous_function = Some(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand `dump` to the functions its header names.
+    fn split_by_header<E>(
+        &self,
+        dump: &PassDump,
+        previous_function: &mut Option<String>,
+        sink: &mut impl FnMut(Target, PassDump) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for target in header_targets(dump, previous_function) {
+            if let Target::Function(name) = &target {
+                *previous_function = Some(name.clone());
+            }
+            sink(target, dump.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Resolve the function a `-print-module-scope` dump belongs to, if any, and
+    /// make sure its header names it.
+    fn attribute_full_dump(
+        &self,
+        pass: PassDump,
+        previous_function: &mut Option<String>,
+    ) -> PassDump {
+        // Loops belong to the function whose passes were dumped right before them
+        let affected_function = match pass.affected_function {
+            Some(ref func) if func.starts_with('%') => previous_function.clone(),
+            ref func => func.clone(),
+        };
+        let Some(func_name) = affected_function else {
+            *previous_function = None;
+            return pass;
+        };
+        // New pass manager headers already name the function ("... on foo")
+        let header = if affected_function_from_header(&pass.header).as_ref() == Some(&func_name) {
+            pass.header
+        } else {
+            // Keep `(invalidated)` last, for matching the header with its Before
+            let (header, invalidated) = match pass.header.strip_suffix(" (invalidated)") {
+                Some(header) => (header, " (invalidated)"),
+                None => (pass.header.as_str(), ""),
+            };
+            format!("{} ({}){}", header, func_name, invalidated)
+        };
+        *previous_function = Some(func_name.clone());
+        PassDump {
+            header,
+            affected_function: Some(func_name),
+            ..pass
         }
-        pass_dumps_by_function
     }
 
     fn associate_full_dumps_with_functions(
@@ -252,160 +583,43 @@ impl LlvmPassDumpParser {
 
         for pass in &pass_dumps {
             if let Some(ref func) = pass.affected_function {
-                if !pass_dumps_by_function.contains_key(func) {
+                if !func.starts_with('%') && !pass_dumps_by_function.contains_key(func) {
                     pass_dumps_by_function.insert(func.clone(), Vec::new());
                 }
             }
         }
 
-        pass_dumps_by_function.insert("<Full Module>".to_string(), Vec::new());
+        pass_dumps_by_function.insert(FULL_MODULE.to_string(), Vec::new());
         let mut previous_function: Option<String> = None;
 
         for pass in pass_dumps {
-            if let Some(ref func) = pass.affected_function {
-                let func_name = if func.starts_with('%') {
-                    previous_function.clone().unwrap()
-                } else {
-                    func.clone()
-                };
+            let pass = self.attribute_full_dump(pass, &mut previous_function);
+            if let Some(ref func_name) = pass.affected_function {
                 pass_dumps_by_function
-                    .get_mut(&func_name)
+                    .get_mut(func_name)
                     .unwrap()
-                    .push(PassDump {
-                        header: format!("{} ({})", pass.header, func_name),
-                        affected_function: Some(func_name.clone()),
-                        machine: pass.machine,
-                        lines: pass.lines.clone(),
-                    });
-                previous_function = Some(func_name);
+                    .push(pass);
             } else {
                 for (_, entry) in pass_dumps_by_function.iter_mut() {
-                    entry.push(PassDump {
-                        header: pass.header.clone(),
-                        affected_function: None,
-                        machine: pass.machine,
-                        lines: pass.lines.clone(),
-                    });
+                    entry.push(pass.clone());
                 }
-                previous_function = None;
             }
         }
         pass_dumps_by_function
     }
 
-    fn match_pass_dumps(
-        &self,
-        pass_dumps_by_function: HashMap<String, Vec<PassDump>>,
-    ) -> Result<OptPipelineResults, PassDumpError> {
-        let mut final_output = HashMap::new();
-
-        for (function_name, pass_dumps) in pass_dumps_by_function {
-            let mut passes: Vec<Pass> = Vec::new();
-            let mut i = 0;
-
-            while i < pass_dumps.len() {
-                let mut pass = Pass {
-                    name: "".to_string(),
-                    machine: false,
-                    after: String::new(),
-                    before: String::new(),
-                    ir_changed: true,
-                };
-                let current_dump = &pass_dumps[i];
-                let next_dump = if i < pass_dumps.len() - 1 {
-                    Some(&pass_dumps[i + 1])
-                } else {
-                    None
-                };
-
-                if current_dump.header.starts_with("IR Dump After ") {
-                    pass.name = current_dump.header["IR Dump After ".len()..].to_string();
-                    pass.after = current_dump.lines.clone();
-                    i += 1;
-                } else if current_dump.header.starts_with("IR Dump Before ") {
-                    if let Some(next_dump) = next_dump {
-                        if next_dump.header.starts_with("IR Dump After ") {
-                            passes_match(&current_dump.header, &next_dump.header)?;
-                            assert!(current_dump.machine == next_dump.machine);
-                            pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
-                            pass.before = current_dump.lines.clone();
-                            pass.after = next_dump.lines.clone();
-                            i += 2;
-                        } else {
-                            pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
-                            pass.before = current_dump.lines.clone();
-                            i += 1;
-                        }
-                    } else {
-                        pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
-                        pass.before = current_dump.lines.clone();
-                        i += 1;
-                    }
-                } else {
-                    panic!("Unexpected pass header {}", current_dump.header);
-                }
-                pass.machine = current_dump.machine;
-
-                // handle isel diff, and NOT handle machine-outliner (before != after)
-                if let Some(previous_pass) = passes.last() {
-                    if !previous_pass.machine && pass.machine && pass.before != pass.after {
-                        pass.before = previous_pass.after.clone();
-                    }
-                }
-
-                pass.ir_changed = pass.before != pass.after;
-                passes.push(pass);
-            }
-
-            for i in 0..passes.len() {
-                // If 'before' is empty and there’s a previous pass, use its 'after'
-                if passes[i].before.is_empty() && i > 0 {
-                    passes[i].before = passes[i - 1].after.clone();
-                }
-
-                // If 'after' is empty and there’s a next pass, use its 'before'
-                if passes[i].after.is_empty() && i < passes.len() - 1 {
-                    passes[i].after = passes[i + 1].before.clone();
-                }
-
-                // Recalculate ir_changed after filling
-                passes[i].ir_changed = passes[i].before != passes[i].after;
-            }
-
-            final_output.insert(function_name, passes);
-        }
-        Ok(final_output)
-    }
-
-    fn breakdown_output(
+    fn ir_filter(
         &self,
-        ir: &str,
         opt_pipeline_options: &OptPipelineBackendOptions,
-    ) -> Result<OptPipelineResults, PassDumpError> {
-        let raw_passes = self.breakdown_output_into_pass_dumps(ir);
-
-        if opt_pipeline_options.full_module {
-            let pass_dumps_by_function = self.associate_full_dumps_with_functions(raw_passes);
-            Ok(self.match_pass_dumps(pass_dumps_by_function)?)
-        } else {
-            let pass_dumps = raw_passes
-                .into_iter()
-                .map(|dump| self.breakdown_pass_dumps_into_functions(dump))
-                .collect();
-            let pass_dumps_by_function = self.breakdown_into_pass_dumps_by_function(pass_dumps);
-            Ok(self.match_pass_dumps(pass_dumps_by_function)?)
-        }
-    }
-
-    fn apply_ir_filters(
-        &self,
-        ir: &str,
-        opt_pipeline_options: &OptPipelineBackendOptions,
-    ) -> String {
-        let mut inline_filters = vec![r"(?m),? #\d+( \{)?$"];
+    ) -> Result<Regex, PassDumpError> {
+        let mut inline_filters = vec![];
         let mut line_filters = vec![
             r"; ModuleID = '.+'",
             r"(source_filename|target datalayout|target triple) = '.+'",
+        ];
+
+        let attribute_inline_filters = [r"(?m),? #\d+( \{)?$"];
+        let attribute_line_filters = [
             r"; Function Attrs: .+",
             r"declare .+",
             r"attributes #\d+ = \{ .+ \}",
@@ -422,6 +636,11 @@ impl LlvmPassDumpParser {
             r"(![.A-Z_a-z-]+) = (?:distinct )?!\{.*\}.*",           // appended .*
         ];
 
+        if opt_pipeline_options.filter_attributes {
+            line_filters.extend(attribute_line_filters);
+            inline_filters.extend(attribute_inline_filters);
+        }
+
         if opt_pipeline_options.filter_debug_info {
             line_filters.extend(debug_line_filters);
             inline_filters.extend(debug_inline_filters);
@@ -431,91 +650,2077 @@ impl LlvmPassDumpParser {
             inline_filters.extend(metadata_inline_filters);
         }
 
-        let line_re = line_filters
-            .into_iter()
-            .map(|re| format!(r"(?:{})", re))
-            .join("|")
-            .to_string();
-        let line_re = format!(r"(?m)^(:?{})(?:\r\n|\n|\r)", line_re);
+        // User rules remove every line they match in, not just lines they match in full
+        let user_line_filters: Vec<String> = opt_pipeline_options
+            .line_filters
+            .iter()
+            .map(|re| format!(".*(?:{}).*", re))
+            .collect();
+        line_filters.extend(user_line_filters.iter().map(String::as_str));
+        inline_filters.extend(
+            opt_pipeline_options
+                .inline_filters
+                .iter()
+                .map(String::as_str),
+        );
 
-        let inline_re = inline_filters
+        let line_re = line_filters
             .into_iter()
             .map(|re| format!(r"(?:{})", re))
             .join("|")
             .to_string();
+        let line_re = format!(r"(?m)^(?:{})(?:\r\n|\n|\r)", line_re);
 
-        let combined = format!("(:?{})|(:?{})", line_re, inline_re);
-        let re = Regex::new(&combined).unwrap();
+        let combined = if inline_filters.is_empty() {
+            line_re
+        } else {
+            let inline_re = inline_filters
+                .into_iter()
+                .map(|re| format!(r"(?:{})", re))
+                .join("|")
+                .to_string();
+            format!("(?:{})|(?:{})", line_re, inline_re)
+        };
+        Ok(Regex::new(&combined)?)
+    }
 
-        re.replace_all(ir, "").to_string()
+    /// Whether function-scoped passes in this dump print the whole module, i.e. the
+    /// dump was produced with `-print-module-scope`.
+    fn is_full_module_dump(&self, output: &[u8]) -> bool {
+        let mut lines = utf8_lines(output)
+            .filter(|line| !line.trim().is_empty())
+            .peekable();
+        while let Some(line) = lines.next() {
+            let Some(captures) = self.ir_dump_header.captures(line) else {
+                continue;
+            };
+            let header = captures.get(1).or(captures.get(2)).unwrap().as_str();
+            if captures.get(3).is_none() && affected_function_from_header(header).is_none() {
+                continue;
+            }
+            // `-print-changed` leaves out the IR of passes that didn't change it
+            match lines.peek() {
+                Some(next) if is_header(next) => continue,
+                next => return next.is_some_and(|line| line.starts_with("; ModuleID = ")),
+            }
+        }
+        false
     }
 
-    fn process<'a>(
+    fn process<L: LineSource>(
         &self,
-        output: &'a str,
+        input: L,
         opt_pipeline_options: &OptPipelineBackendOptions,
-    ) -> Result<(&'a str, OptPipelineResults), PassDumpError> {
-        let offset = {
-            let mut pos = 0;
-            let newlines = memchr_iter(b'\n', output.as_bytes());
-
-            for newline_pos in newlines {
-                let line = &output[pos..newline_pos];
-                if self.ir_dump_header.is_match(line)
-                    || self.machine_code_dump_header.is_match(line)
+    ) -> Result<(String, OptPipelineResults, Vec<PassDumpError>), PassDumpError> {
+        let mut reader = PassDumpReader::new(self, input, opt_pipeline_options)?;
+        // Pipelines by module and function, so interleaved compilers don't mix
+        let mut pipelines: HashMap<(usize, String), PipelineBuilder> = HashMap::new();
+        let new_pipeline = || PipelineBuilder::new(opt_pipeline_options.snapshots);
+
+        if opt_pipeline_options.full_module {
+            // Module passes go to every function, so all of them have to be known first
+            let mut pass_dumps_by_module: Vec<Vec<PassDump>> = Vec::new();
+            for dump in reader.by_ref() {
+                let dump = dump?;
+                if pass_dumps_by_module.len() <= dump.module {
+                    pass_dumps_by_module.resize_with(dump.module + 1, Vec::new);
+                }
+                pass_dumps_by_module[dump.module].push(dump);
+            }
+            for (module, pass_dumps) in pass_dumps_by_module.into_iter().enumerate() {
+                if pass_dumps.is_empty() {
+                    continue;
+                }
+                for (function_name, pass_dumps) in
+                    self.associate_full_dumps_with_functions(pass_dumps)
                 {
-                    break;
+                    let pipeline = pipelines
+                        .entry((module, function_name))
+                        .or_insert_with(new_pipeline);
+                    for dump in pass_dumps {
+                        reader.diagnostics.recover(pipeline.push(dump))?;
+                    }
                 }
-                pos = newline_pos + 1;
             }
-            pos
-        };
-        let ir = &output[offset..];
-        let ir = match opt_pipeline_options.apply_filters {
-            true => &self.apply_ir_filters(ir, opt_pipeline_options),
-            false => ir,
-        };
-        Ok((
-            &output[..offset],
-            self.breakdown_output(ir, opt_pipeline_options)?,
-        ))
+        } else {
+            let mut split_states: HashMap<usize, SplitState> = HashMap::new();
+            while let Some(dump) = reader.next_dump()? {
+                let module = dump.module;
+                let state = split_states.entry(module).or_default();
+                let diagnostics = &mut reader.diagnostics;
+                let split = self.split_pass_dump(dump, state, |target, dump| match target {
+                    Target::Function(name) => diagnostics.recover(
+                        pipelines
+                            .entry((module, name))
+                            .or_insert_with(new_pipeline)
+                            .push(dump),
+                    ),
+                    Target::All => pipelines
+                        .iter_mut()
+                        .filter(|((pipeline_module, _), _)| *pipeline_module == module)
+                        .try_for_each(|(_, pipeline)| {
+                            diagnostics.recover(pipeline.push(dump.clone()))
+                        }),
+                });
+                diagnostics.recover(split)?;
+            }
+        }
+
+        let mut modules_by_function: HashMap<String, usize> = HashMap::new();
+        for (_, function_name) in pipelines.keys() {
+            *modules_by_function
+                .entry(function_name.clone())
+                .or_default() += 1;
+        }
+        let results = pipelines
+            .into_iter()
+            .map(|((module, function_name), pipeline)| {
+                // Functions compiled by several of the interleaved compilers, e.g.
+                // inline functions from headers, are told apart by their module
+                let name = match modules_by_function[&function_name] {
+                    1 => function_name,
+                    _ => reader.modules.qualified_name(&function_name, module),
+                };
+                (name, pipeline.finish(reader.unfinished_pass(module)))
+            })
+            .collect();
+        Ok((reader.prefix, results, reader.diagnostics.warnings))
     }
 }
 
-fn passes_match(before: &str, after: &str) -> Result<(), PassDumpError> {
-    assert!(before.starts_with("IR Dump Before "));
-    assert!(after.starts_with("IR Dump After "));
-    let before = &before["IR Dump Before ".len()..];
-    let mut after = &after["IR Dump After ".len()..];
-    if after.ends_with(" (invalidated)") {
-        after = &after[..after.len() - " (invalidated)".len()];
+fn is_header(line: &str) -> bool {
+    if line.starts_with(DIFF_SECTION) {
+        return false;
     }
+    line.starts_with("; *** ")
+        || line.starts_with("*** ")
+        || line.starts_with("// -----// ")
+        || line.starts_with("# *** ")
+}
 
-    if before == after {
-        Ok(())
-    } else {
-        Err(PassDumpError::PassMismatch {
-            before_header: before.to_string(),
-            after_header: after.to_string(),
-        })
+/// Where [`PassDumpReader`] gets its lines from.
+trait LineSource {
+    /// The next line including its line ending, or `None` at the end of the input.
+    fn next_line(&mut self) -> io::Result<Option<&str>>;
+}
+
+/// Lines borrowed straight from a dump that is already in memory. Each line is
+/// checked to be UTF-8 as it is read, so a mapped file is never trusted up front.
+impl LineSource for &[u8] {
+    fn next_line(&mut self) -> io::Result<Option<&str>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let end = memchr(b'\n', self).map_or(self.len(), |pos| pos + 1);
+        let (line, rest) = self.split_at(end);
+        *self = rest;
+        std::str::from_utf8(line)
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
     }
 }
 
-pub fn process(
-    dump: &str,
-    apply_filters: bool,
-) -> Result<(&str, OptPipelineResults), PassDumpError> {
-    let llvm_pass_dump_parser = LlvmPassDumpParser::new();
-    llvm_pass_dump_parser.process(
-        dump,
-        &OptPipelineBackendOptions {
-            filter_debug_info: true,
-            filter_ir_metadata: true,
-            full_module: false,
-            no_discard_value_names: false,
-            demangle: false,
-            library_functions: false,
-            apply_filters,
-        },
-    )
+/// The lines of `dump` that are valid UTF-8, for telling what kind of dump it is.
+fn utf8_lines(dump: &[u8]) -> impl Iterator<Item = &str> {
+    dump.split(|&c| c == b'\n')
+        .filter_map(|line| std::str::from_utf8(line).ok())
+        .map(trim_line_ending)
+}
+
+/// Lines read one at a time from a file or pipe.
+struct ReadLines<R> {
+    input: R,
+    line: String,
+}
+
+impl<R: BufRead> LineSource for ReadLines<R> {
+    fn next_line(&mut self) -> io::Result<Option<&str>> {
+        self.line.clear();
+        match self.input.read_line(&mut self.line)? {
+            0 => Ok(None),
+            _ => Ok(Some(&self.line)),
+        }
+    }
+}
+
+/// Reads a dump one pass at a time, so that only the current pass dump is held in
+/// memory rather than the whole input.
+struct PassDumpReader<'p, L> {
+    parser: &'p LlvmPassDumpParser,
+    source: L,
+    filter: Option<Regex>,
+    /// Compiler output before the first pass dump
+    prefix: String,
+    in_prefix: bool,
+    /// Whether every line is tagged with the compiler it comes from, like `[1234] `
+    tagged: bool,
+    /// Pass dumps being read, one per compiler writing to the dump
+    streams: Vec<DumpStream>,
+    /// Stream of the last line
+    current: usize,
+    /// Number of lines read
+    line_number: usize,
+    /// Pass dumps read but not yet returned
+    ready: VecDeque<Result<PassDump, PassDumpError>>,
+    at_end: bool,
+    diagnostics: Diagnostics,
+    modules: ModuleTracker,
+    /// Buffer reused for every pass dump
+    scratch: String,
+    /// Known once the first function-scoped pass dump or function printed by itself
+    /// has been read
+    full_module: Option<bool>,
+    befores: usize,
+    has_after: bool,
+    /// Line and kind of the last pass dump of each module
+    last_dumps: HashMap<usize, (usize, DumpKind)>,
+}
+
+/// The pass dump being read from one compiler.
+#[derive(Default)]
+struct DumpStream {
+    /// The tag of its lines, if the dump is tagged
+    tag: Option<String>,
+    /// Header of the pass dump and its line
+    header: Option<(String, usize)>,
+    /// Reused for every pass dump
+    body: String,
+    /// The first function of the pass dump without a name, at its line
+    malformed_function: Option<(String, usize)>,
+}
+
+impl<'p, L: LineSource> PassDumpReader<'p, L> {
+    fn new(
+        parser: &'p LlvmPassDumpParser,
+        source: L,
+        options: &OptPipelineBackendOptions,
+    ) -> Result<Self, PassDumpError> {
+        let filter = match options.apply_filters {
+            true => Some(parser.ir_filter(options)?),
+            false => None,
+        };
+        Ok(Self {
+            parser,
+            source,
+            filter,
+            prefix: String::new(),
+            in_prefix: true,
+            tagged: false,
+            streams: vec![DumpStream::default()],
+            current: 0,
+            line_number: 0,
+            ready: VecDeque::new(),
+            at_end: false,
+            diagnostics: Diagnostics {
+                lenient: options.lenient,
+                warnings: Vec::new(),
+            },
+            modules: ModuleTracker::default(),
+            scratch: String::new(),
+            full_module: None,
+            befores: 0,
+            has_after: false,
+            last_dumps: HashMap::new(),
+        })
+    }
+
+    /// Whether several compilers write to the dump, as far as it has been read.
+    fn interleaved(&self) -> bool {
+        self.tagged || self.modules.names.len() > 1
+    }
+
+    /// Which side of the passes the dump doesn't print, once that is known.
+    fn missing_ir(&self, at_end: bool) -> Option<Option<MissingIr>> {
+        match (self.befores, self.has_after) {
+            (0, false) => None,
+            // Dumps with both start with an `IR Dump Before`
+            (0, true) => Some(Some(MissingIr::Before)),
+            (_, true) => Some(None),
+            (1, false) if !at_end => None,
+            (_, false) => Some(Some(MissingIr::After)),
+        }
+    }
+
+    fn next_dump(&mut self) -> Result<Option<PassDump>, PassDumpError> {
+        loop {
+            match self.read_dump() {
+                Ok(Some(dump)) if dump.kind == DumpKind::Ignored => continue,
+                Err(error) => self.diagnostics.recover(Err(error))?,
+                Ok(Some(dump)) => {
+                    self.last_dumps.insert(dump.module, (dump.line, dump.kind));
+                    return Ok(Some(dump));
+                }
+                Ok(None) => return Ok(None),
+            }
+        }
+    }
+
+    /// Line of the `IR Dump Before` the dump of `module` ends with, which is the
+    /// pass the compiler was in when it stopped, e.g. because it crashed.
+    fn unfinished_pass(&self, module: usize) -> Option<usize> {
+        match self.last_dumps.get(&module) {
+            Some(&(line, DumpKind::Before)) => Some(line),
+            _ => None,
+        }
+    }
+
+    fn read_dump(&mut self) -> Result<Option<PassDump>, PassDumpError> {
+        while self.ready.is_empty() {
+            let raw_line = match self.source.next_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    if !self.at_end {
+                        self.at_end = true;
+                        for stream in 0..self.streams.len() {
+                            self.finish_dump(stream);
+                        }
+                    }
+                    break;
+                }
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                    return Err(PassDumpError::InvalidUtf8 {
+                        line: self.line_number + 1,
+                    });
+                }
+                Err(error) => return Err(error.into()),
+            };
+            self.line_number += 1;
+            let mut line = trim_line_ending(raw_line);
+            if self.in_prefix {
+                let untagged = split_tag(line).map_or(line, |(_, rest)| rest);
+                if self.parser.ir_dump_header.is_match(untagged)
+                    || self.parser.machine_code_dump_header.is_match(untagged)
+                {
+                    self.in_prefix = false;
+                    self.tagged = untagged.len() != line.len();
+                } else {
+                    self.prefix.push_str(raw_line);
+                    continue;
+                }
+            }
+            if self.tagged {
+                // Untagged lines continue the line before them
+                if let Some((tag, rest)) = split_tag(line) {
+                    self.current = match self
+                        .streams
+                        .iter()
+                        .position(|stream| stream.tag.as_deref() == Some(tag))
+                    {
+                        Some(stream) => stream,
+                        None if self.streams[0].tag.is_none() => {
+                            self.streams[0].tag = Some(tag.to_string());
+                            0
+                        }
+                        None => {
+                            self.streams.push(DumpStream {
+                                tag: Some(tag.to_string()),
+                                ..Default::default()
+                            });
+                            self.streams.len() - 1
+                        }
+                    };
+                    line = rest;
+                }
+            }
+
+            let stream = &mut self.streams[self.current];
+            if is_header(line) {
+                let header = (line.to_string(), self.line_number);
+                self.finish_dump(self.current);
+                self.streams[self.current].header = Some(header);
+            } else if stream.header.is_some() {
+                // Lines of the body aren't numbered once they are parsed, they may be
+                // interleaved with other dumps' and filtered
+                if stream.malformed_function.is_none() && function_start(line) == Some(None) {
+                    stream.malformed_function = Some((line.to_string(), self.line_number));
+                }
+                stream.body.push_str(line);
+                stream.body.push('\n');
+            }
+        }
+        self.ready.pop_front().transpose()
+    }
+
+    /// Parse the pass dump read so far from `stream`, if any.
+    fn finish_dump(&mut self, index: usize) {
+        let stream = &mut self.streams[index];
+        let Some((header, line_number)) = stream.header.take() else {
+            return;
+        };
+        let body = std::mem::take(&mut stream.body);
+        let tag = stream.tag.clone();
+        let malformed_function = stream.malformed_function.take();
+
+        let dump = self.parser.parse_pass_dump(
+            &header,
+            line_number,
+            &body,
+            self.filter.as_ref(),
+            &mut self.scratch,
+        );
+        let dump = match malformed_function {
+            Some((snippet, line)) => {
+                dump.and(Err(PassDumpError::MalformedFunction { line, snippet }))
+            }
+            None => dump,
+        };
+        let dump = dump.map(|dump| self.place_dump(dump, tag.as_deref(), &body));
+        self.ready.push_back(dump);
+
+        // Keep the buffer for the next pass dump
+        let stream = &mut self.streams[index];
+        stream.body = body;
+        stream.body.clear();
+    }
+
+    /// Find the module of `dump` from its `tag` and unfiltered `body`, and learn
+    /// the shape of the dump from it.
+    fn place_dump(&mut self, mut dump: PassDump, tag: Option<&str>, body: &str) -> PassDump {
+        dump.module = self.modules.module_of(tag, body, &dump);
+        self.befores += usize::from(dump.kind == DumpKind::Before);
+        self.has_after |= dump.header.starts_with("IR Dump After");
+        dump.module = self
+            .modules
+            .settle(&dump, self.befores > 0 && self.has_after);
+        // `-print-changed` leaves out the IR of passes that didn't change it
+        let has_ir = !body.trim().is_empty();
+        if self.full_module.is_none() && has_ir {
+            let module_scope = body
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .is_some_and(|line| line.starts_with("; ModuleID = "));
+            // Module passes print the module either way, but a function printed by
+            // itself, like the function passes of the legacy pass manager or Machine
+            // passes, tells that the dump isn't module-scoped
+            if dump.affected_function.is_some() || !module_scope {
+                self.full_module = Some(module_scope);
+            }
+        }
+        dump
+    }
+}
+
+impl<L: LineSource> Iterator for PassDumpReader<'_, L> {
+    type Item = Result<PassDump, PassDumpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_dump().transpose()
+    }
+}
+
+#[cfg(test)]
+mod crashed_tests {
+    use super::tests::{pass_irs, process_ok, stream_ok};
+    use super::*;
+
+    #[test]
+    fn crashed_dump() {
+        let dump = include_str!("../tests/fixtures/print-before-after.txt");
+        // The compiler died in the pass, before printing the IR after it
+        let dump = &dump[..dump
+            .find("*** IR Dump After InstCombinePass on sum")
+            .unwrap()];
+        let options = OptPipelineBackendOptions::default();
+        let result = process_ok(dump.as_bytes(), &options);
+        let last = result["sum"].last().unwrap();
+        assert_eq!(last.name, "InstCombinePass on sum");
+        assert!(last.crashed);
+        assert!(last.before.starts_with("define i32 @sum("));
+        assert!(last.after.is_empty());
+        let crashed: Vec<_> = result
+            .values()
+            .flatten()
+            .filter(|pass| pass.crashed)
+            .collect();
+        assert_eq!(crashed.len(), 1);
+
+        let streamed = stream_ok(dump.as_bytes(), &options, "sum");
+        assert!(streamed.last().unwrap().crashed);
+        assert_eq!(pass_irs(&streamed), pass_irs(&result["sum"]));
+
+        // Stopping after a pass is not crashing in the next one
+        let dump = &dump[..dump
+            .find("*** IR Dump Before InstCombinePass on sum")
+            .unwrap()];
+        let result = process_ok(dump.as_bytes(), &options);
+        assert!(result.values().flatten().all(|pass| !pass.crashed));
+    }
+}
+
+/// A dump header without the IR unit the pass ran on, like `IR Dump Before SROAPass`
+/// for `IR Dump Before SROAPass on foo`.
+fn pass_name(header: &str) -> &str {
+    header.split_once(" on ").map_or(header, |(pass, _)| pass)
+}
+
+/// Split a line tagged with the compiler it comes from, like `[1234] *** IR Dump`,
+/// into the tag and the line.
+fn split_tag(line: &str) -> Option<(&str, &str)> {
+    let (tag, rest) = line.strip_prefix('[')?.split_once("] ")?;
+    (!tag.is_empty() && !tag.contains(char::is_whitespace)).then_some((tag, rest))
+}
+
+/// Tells apart the modules of several compilers writing to the same dump, by the
+/// tags of their lines or else by the `; ModuleID` of module dumps and the
+/// functions those define.
+struct ModuleTracker {
+    /// Name of each module, empty until it is known
+    names: Vec<String>,
+    /// Modules defining each function
+    defined_in: HashMap<String, Vec<usize>>,
+    /// Last dump of each module
+    last: Vec<Option<PassDump>>,
+    /// Passes seen to be dumped right after each pass, in any module
+    successors: HashMap<String, HashSet<String>>,
+    current: usize,
+    /// Whether the module of the current dump is known from its tag or module ID
+    certain: bool,
+}
+
+impl Default for ModuleTracker {
+    fn default() -> Self {
+        Self {
+            names: vec![String::new()],
+            defined_in: HashMap::new(),
+            last: Vec::new(),
+            successors: HashMap::new(),
+            current: 0,
+            certain: false,
+        }
+    }
+}
+
+impl ModuleTracker {
+    /// The module `dump` belongs to, given the `tag` of its lines and its unfiltered
+    /// `body`. Dumps that don't tell go to the module of the dump before them.
+    fn module_of(&mut self, tag: Option<&str>, body: &str, dump: &PassDump) -> usize {
+        let module_id = || {
+            let line = body.lines().find(|line| !line.trim().is_empty())?;
+            line.strip_prefix("; ModuleID = '")?.strip_suffix('\'')
+        };
+        let name = tag.or_else(module_id);
+        self.certain = name.is_some();
+        if let Some(name) = name {
+            if let Some(module) = self.names.iter().position(|module| module == name) {
+                self.current = module;
+                return module;
+            }
+            // The first module is named by the first name there is
+            if self.names[self.current].is_empty() {
+                self.names[self.current] = name.to_string();
+            } else {
+                self.names.push(name.to_string());
+                self.current = self.names.len() - 1;
+            }
+            // The first module dump of an untagged module defines its functions
+            if tag.is_none() {
+                self.add_functions(&dump.lines);
+            }
+        } else if let Some(modules) = dump
+            .affected_function
+            .as_ref()
+            .and_then(|function| self.defined_in.get(function))
+        {
+            if !modules.contains(&self.current) {
+                self.current = *modules.last().unwrap();
+            }
+        }
+        self.current
+    }
+
+    /// Settle which of the modules defining the function of `dump` it belongs to (any
+    /// module for loops) when its tag or module ID doesn't tell: one whose last dump
+    /// it can follow, preferably one where the same passes followed each other
+    /// before, starting with its guessed module. Dumps come in Before/After pairs if
+    /// `paired`.
+    fn settle(&mut self, dump: &PassDump, paired: bool) -> usize {
+        // Nothing to tell apart in the output of a single compiler
+        if self.names.len() == 1 {
+            return self.current;
+        }
+        if !self.certain {
+            let all_modules = (0..self.names.len()).collect::<Vec<_>>();
+            let modules = match dump_function(dump) {
+                Some(function) if function.starts_with('%') => Some(&all_modules),
+                function => function.and_then(|function| self.defined_in.get(function)),
+            };
+            if let Some(modules) = modules.filter(|modules| modules.len() > 1) {
+                let mut best = None;
+                for &module in [dump.module].iter().chain(modules) {
+                    if !self.follows(module, dump, paired) {
+                        continue;
+                    }
+                    let rank = self.rank(module, dump);
+                    if best.is_none_or(|(_, best_rank)| rank > best_rank) {
+                        best = Some((module, rank));
+                    }
+                }
+                if let Some((module, _)) = best {
+                    self.current = module;
+                }
+            } else {
+                self.current = dump.module;
+            }
+        }
+        if self.last.len() <= self.current {
+            self.last.resize(self.current + 1, None);
+        }
+        if let Some(last) = &self.last[self.current] {
+            self.successors
+                .entry(pass_name(&last.header).to_string())
+                .or_default()
+                .insert(pass_name(&dump.header).to_string());
+        }
+        self.last[self.current] = Some(dump.clone());
+        self.current
+    }
+
+    /// How well `dump` fits in after the last dump of `module` by the passes seen to
+    /// follow that one: 2 if the pass was seen, 1 if none were and 0 otherwise.
+    fn rank(&self, module: usize, dump: &PassDump) -> u8 {
+        let Some(Some(last)) = self.last.get(module) else {
+            return 1;
+        };
+        match self.successors.get(pass_name(&last.header)) {
+            Some(successors) if successors.contains(pass_name(&dump.header)) => 2,
+            Some(_) => 0,
+            None => 1,
+        }
+    }
+
+    /// Whether `dump` can be the next dump of `module`: an After follows the Before
+    /// of the same pass, and the IR before a pass is the IR after the last pass on
+    /// the same function.
+    fn follows(&self, module: usize, dump: &PassDump, paired: bool) -> bool {
+        let Some(Some(last)) = self.last.get(module) else {
+            return true;
+        };
+        match (last.kind, dump.kind) {
+            (DumpKind::Before, DumpKind::After) => passes_match(last, dump).is_ok(),
+            (DumpKind::After, DumpKind::Before) => {
+                let (after, before) = (last.lines.trim(), dump.lines.trim());
+                if is_loop_ir(before) && !is_loop_ir(after) {
+                    extract_loop(after, before).trim() == before
+                } else {
+                    dump_function(last) != dump_function(dump) || after == before
+                }
+            }
+            (DumpKind::Before, DumpKind::Before) | (DumpKind::After, DumpKind::After) => !paired,
+            _ => true,
+        }
+    }
+
+    /// Remember the functions defined in a dump of the current module.
+    fn add_functions(&mut self, ir: &str) {
+        for line in ir.lines() {
+            let Some(define) = line.strip_prefix("define ") else {
+                continue;
+            };
+            let Some(name) = define
+                .split_once('@')
+                .and_then(|(_, name)| name.split_once('('))
+            else {
+                continue;
+            };
+            match self.defined_in.get_mut(name.0) {
+                Some(modules) if modules.contains(&self.current) => {}
+                Some(modules) => modules.push(self.current),
+                None => {
+                    self.defined_in
+                        .insert(name.0.to_string(), vec![self.current]);
+                }
+            }
+        }
+    }
+
+    /// A function name that tells which module it is from, like `foo [a.c]`.
+    fn qualified_name(&self, function: &str, module: usize) -> String {
+        format!("{} [{}]", function, self.names[module])
+    }
+}
+
+#[cfg(test)]
+mod interleaved_tests {
+    use super::tests::{pass_irs, process_ok};
+    use super::*;
+
+    /// The lines of `dump` tagged with `tag`, without the tag.
+    fn untagged(dump: &[u8], tag: &str) -> String {
+        let prefix = format!("[{}] ", tag);
+        let lines = utf8_lines(dump).filter_map(|line| line.strip_prefix(&prefix));
+        lines.map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn tagged_interleaved_dumps() {
+        let dump = include_bytes!("../tests/fixtures/interleaved-tagged.txt");
+        assert!(is_interleaved(dump));
+        let options = OptPipelineBackendOptions::default();
+        let result = process_ok(dump, &options);
+        assert_eq!(result.len(), 6);
+        for tag in ["101", "202"] {
+            let single = untagged(dump, tag);
+            assert!(!is_interleaved(single.as_bytes()));
+            for (function, passes) in process_ok(single.as_bytes(), &options) {
+                // Functions both compilers define are told apart by the tag
+                let name = match function.as_str() {
+                    "sum" | "total" => function.clone(),
+                    _ => format!("{} [{}]", function, tag),
+                };
+                assert_eq!(pass_irs(&result[&name]), pass_irs(&passes));
+            }
+        }
+    }
+
+    #[test]
+    fn module_scope_interleaved_dumps() {
+        let dump = include_bytes!("../tests/fixtures/interleaved-module-scope.txt");
+        assert!(is_interleaved(dump));
+        let result = process_ok(dump, &Default::default());
+        let mut functions: Vec<_> = result.keys().map(String::as_str).collect();
+        functions.sort();
+        assert_eq!(
+            functions,
+            [
+                "cube [cg.ll]",
+                "cube [cg2.ll]",
+                "square [cg.ll]",
+                "square [cg2.ll]",
+                "sum",
+                "total"
+            ]
+        );
+        for (caller, module) in [("sum", "cg.ll"), ("total", "cg2.ll")] {
+            let inliner = &result[caller][3];
+            assert_eq!(inliner.name, format!("InlinerPass on ({})", caller));
+            assert!(inliner
+                .after
+                .starts_with(&format!("define i32 @{}(", caller)));
+            assert!(inliner.after.contains("%mul.i = mul nsw i32 %val, %val"));
+            // Deleted once inlined into the caller of its own module
+            let square = result[&format!("square [{}]", module)].last().unwrap();
+            assert_eq!(square.name, inliner.name);
+            assert!(square.after.is_empty());
+        }
+    }
+}
+
+fn changed(before: &Arc<str>, after: &Arc<str>) -> bool {
+    !Arc::ptr_eq(before, after) && before != after
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Builds the pipeline of one function from its pass dumps as they are read.
+/// A pass is only complete once the next one is known, since an empty `before` or
+/// `after` is taken from its neighbours.
+#[derive(Default)]
+struct PipelineBuilder {
+    /// `IR Dump Before` waiting for the matching `IR Dump After`
+    pending_before: Option<PassDump>,
+    /// IR of the whole function as of the last pass, with the changes of loop
+    /// passes merged in. Seeded with `IR Dump At Start` by `-print-changed`
+    function_ir: Option<Arc<str>>,
+    /// Last pass, whose `after` may still come from the next pass's `before`
+    last: Option<Pass>,
+    /// Whether the last pass's `after` is known, even if empty
+    last_after_known: bool,
+    /// Whether any dump printed the IR after a pass
+    has_after: bool,
+    /// Whether any `IR Dump Before` was followed by its `IR Dump After`
+    paired: bool,
+    /// Diff every dump against the one before it, see [`Self::push_snapshot`]
+    snapshots: bool,
+    /// Label and IR of the last snapshot
+    snapshot: Option<(String, Arc<str>)>,
+    done: Vec<Pass>,
+}
+
+impl PipelineBuilder {
+    fn new(snapshots: bool) -> Self {
+        Self {
+            snapshots,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, dump: PassDump) -> Result<(), PassDumpError> {
+        if self.snapshots {
+            return self.push_snapshot(dump);
+        }
+        // Leave the pipeline as it is, so that only the unexpected dump is skipped
+        if dump.kind == DumpKind::Unknown {
+            return Err(unexpected_header(&dump));
+        }
+        self.has_after |= dump.kind != DumpKind::Before;
+        if let Some(before) = self.pending_before.take() {
+            let name = before.header["IR Dump Before ".len()..].to_string();
+            if dump.kind == DumpKind::After && dump.before.is_none() {
+                passes_match(&before, &dump)?;
+                if before.machine != dump.machine {
+                    return Err(PassDumpError::MixedIr {
+                        line: dump.line,
+                        snippet: name,
+                    });
+                }
+                self.add_pass(
+                    name,
+                    before.line,
+                    before.machine,
+                    before.lines,
+                    Some(dump.lines),
+                    false,
+                );
+                self.paired = true;
+                return Ok(());
+            }
+            self.add_pass(
+                name,
+                before.line,
+                before.machine,
+                before.lines,
+                Some(Arc::default()),
+                false,
+            );
+        }
+
+        let name = || dump.header["IR Dump After ".len()..].to_string();
+        match dump.kind {
+            DumpKind::Before => self.pending_before = Some(dump),
+            DumpKind::After => match &dump.before {
+                Some(before) => {
+                    let (before, after) = self.reconstruct_diff(before, &dump.lines);
+                    self.add_pass(name(), dump.line, dump.machine, before, Some(after), true);
+                }
+                None => {
+                    let after = dump.lines.clone();
+                    let (line, machine) = (dump.line, dump.machine);
+                    self.add_pass(name(), line, machine, Arc::default(), Some(after), false);
+                }
+            },
+            DumpKind::Unchanged => {
+                let (line, machine) = (dump.line, dump.machine);
+                self.add_pass(name(), line, machine, Arc::default(), None, true);
+            }
+            DumpKind::Deleted => {
+                self.add_pass(
+                    name(),
+                    dump.line,
+                    dump.machine,
+                    Arc::default(),
+                    Some(Arc::default()),
+                    true,
+                );
+            }
+            DumpKind::Start => {
+                if self.last.is_none() {
+                    self.function_ir = Some(dump.lines);
+                }
+            }
+            DumpKind::Ignored | DumpKind::Unknown => {}
+        }
+        Ok(())
+    }
+
+    /// Add a pass from the last snapshot to `dump`. With `-print-after=instcombine,gvn`
+    /// the passes between two dumps aren't dumped, so the pass is named after the
+    /// span, like `after InstCombinePass → after GVNPass`.
+    fn push_snapshot(&mut self, dump: PassDump) -> Result<(), PassDumpError> {
+        let ir = match dump.kind {
+            DumpKind::Before | DumpKind::After | DumpKind::Start => dump.lines,
+            DumpKind::Deleted => Arc::default(),
+            DumpKind::Unchanged | DumpKind::Ignored => return Ok(()),
+            DumpKind::Unknown => return Err(unexpected_header(&dump)),
+        };
+        let label = snapshot_label(&dump.header);
+        // The first snapshot is all there is to the IR before it
+        let (name, before) = match self.snapshot.take() {
+            Some((previous, previous_ir)) => (
+                format!("{previous} → {label}"),
+                self.ir_before(&previous_ir, &ir),
+            ),
+            None => (label.clone(), ir.clone()),
+        };
+        self.update_function_ir(&ir);
+        self.done.push(Pass {
+            name,
+            machine: dump.machine,
+            ir_changed: changed(&before, &ir),
+            before,
+            after: ir.clone(),
+            crashed: false,
+            line: dump.line,
+        });
+        self.snapshot = Some((label, ir));
+        Ok(())
+    }
+
+    /// Add a pass. An `after` of `None` means the pass didn't change the IR, and
+    /// `after_known` keeps an empty `after` from being filled in by the next pass.
+    fn add_pass(
+        &mut self,
+        name: String,
+        line: usize,
+        machine: bool,
+        mut before: Arc<str>,
+        after: Option<Arc<str>>,
+        after_known: bool,
+    ) {
+        // handle isel diff, and NOT handle machine-outliner (before != after)
+        if let (Some(previous_pass), Some(after)) = (&self.last, &after) {
+            if !previous_pass.machine && machine && before != *after {
+                before = previous_pass.after.clone();
+            }
+        }
+
+        let mut pass = Pass {
+            name,
+            machine,
+            after: after.clone().unwrap_or_default(),
+            before,
+            ir_changed: true,
+            crashed: false,
+            line,
+        };
+        if let Some(mut previous_pass) = self.last.take() {
+            // If 'after' is empty, use the next pass's 'before'
+            if previous_pass.after.is_empty() && !self.last_after_known {
+                previous_pass.after = pass.before.clone();
+                self.update_function_ir(&previous_pass.after);
+            }
+            previous_pass.ir_changed = changed(&previous_pass.before, &previous_pass.after);
+
+            // If 'before' is empty, use the previous pass's 'after'
+            if pass.before.is_empty() {
+                pass.before = self.ir_before(&previous_pass.after, &pass.after);
+            }
+            self.done.push(previous_pass);
+        } else if pass.before.is_empty() {
+            // Without `IR Dump At Start`, the IR after the first pass is the first there is
+            pass.before = match &self.function_ir {
+                Some(function_ir) => self.ir_before(function_ir, &pass.after),
+                None => pass.after.clone(),
+            };
+        }
+        if after.is_none() {
+            pass.after = pass.before.clone();
+        }
+        self.update_function_ir(&pass.after);
+        self.last = Some(pass);
+        self.last_after_known = after_known;
+    }
+
+    /// The IR before a pass that only dumped its `after`. Loop passes only print
+    /// the loop, so the IR before them and after them is relative to the function.
+    fn ir_before(&self, previous_after: &Arc<str>, after: &str) -> Arc<str> {
+        match &self.function_ir {
+            Some(function_ir) if is_loop_ir(after) => Arc::from(extract_loop(function_ir, after)),
+            Some(function_ir) if is_loop_ir(previous_after) => function_ir.clone(),
+            _ => previous_after.clone(),
+        }
+    }
+
+    fn update_function_ir(&mut self, ir: &Arc<str>) {
+        if ir.is_empty() {
+            return;
+        }
+        self.function_ir = match &self.function_ir {
+            Some(function_ir) if is_loop_ir(ir) => Some(Arc::from(merge_loop(function_ir, ir))),
+            _ if is_loop_ir(ir) => None,
+            _ => Some(ir.clone()),
+        };
+    }
+
+    /// Put a `-print-changed=diff` body back into the function it belongs to. The
+    /// diff leaves out the define line and the closing brace, which are taken from
+    /// the IR before the pass when the rest of it matches.
+    fn reconstruct_diff(&self, before: &Arc<str>, after: &Arc<str>) -> (Arc<str>, Arc<str>) {
+        let parts = self.function_ir.as_ref().and_then(|state| {
+            let (first, rest) = state.split_once('\n')?;
+            let (interior, last) = rest.trim_end_matches('\n').rsplit_once('\n')?;
+            Some((state, first, interior, last))
+        });
+        match parts {
+            Some((state, first, interior, last))
+                if interior.trim_matches('\n') == before.trim_matches('\n') =>
+            {
+                let after = format!("{}\n{}\n{}", first, after.trim_matches('\n'), last);
+                (state.clone(), Arc::from(after))
+            }
+            // An unchanged body that doesn't match, keep the function as it is
+            Some((state, ..)) if before == after => (state.clone(), state.clone()),
+            _ => (before.clone(), after.clone()),
+        }
+    }
+
+    /// Complete the pipeline. `unfinished_pass` is the line of the `IR Dump Before`
+    /// the whole dump ends with, see [`PassDumpReader::unfinished_pass`].
+    fn finish(mut self, unfinished_pass: Option<usize>) -> Vec<Pass> {
+        let mut crashed = false;
+        if let Some(before) = self.pending_before.take() {
+            // Unless only a few passes are dumped, every other pass has its `IR Dump After`
+            crashed = self.paired && unfinished_pass == Some(before.line);
+            let name = before.header["IR Dump Before ".len()..].to_string();
+            self.add_pass(
+                name,
+                before.line,
+                before.machine,
+                before.lines,
+                Some(Arc::default()),
+                false,
+            );
+        }
+        if let Some(mut last) = self.last.take() {
+            // Nothing follows the last pass of a dump without `IR Dump After`s
+            if !self.has_after && last.after.is_empty() {
+                last.after = last.before.clone();
+            }
+            last.crashed = crashed;
+            last.ir_changed = changed(&last.before, &last.after);
+            self.done.push(last);
+        }
+        self.done
+    }
+}
+
+/// How a snapshot is referred to in pass names, e.g. `after InstCombinePass` for
+/// `IR Dump After InstCombinePass on foo`.
+fn snapshot_label(header: &str) -> String {
+    let label = header.strip_prefix("IR Dump ").unwrap_or(header);
+    let label = label.split_once(" on ").map_or(label, |(label, _)| label);
+    let mut chars = label.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::tests::{pass_names, process_ok};
+    use super::*;
+
+    #[test]
+    fn snapshots_of_selected_passes() {
+        // `-print-after=inline,instcombine`
+        let dump = include_bytes!("../tests/fixtures/print-after-selected.txt");
+        let options = OptPipelineBackendOptions {
+            snapshots: true,
+            ..Default::default()
+        };
+        let result = process_ok(dump, &options);
+        let full = process_ok(
+            include_bytes!("../tests/fixtures/print-before-after.txt"),
+            &Default::default(),
+        );
+        let after = |function: &str, pass: &str| {
+            let passes = &full[function];
+            passes
+                .iter()
+                .find(|p| p.name == pass)
+                .unwrap()
+                .after
+                .clone()
+        };
+
+        let sum = &result["sum"];
+        assert_eq!(
+            pass_names(sum),
+            [
+                "after InlinerPass",
+                "after InlinerPass → after InstCombinePass"
+            ]
+        );
+        assert!(!sum[0].ir_changed);
+        assert!(sum[1].ir_changed);
+        assert_eq!(sum[1].before, after("sum", "InlinerPass on (sum)"));
+        assert_eq!(sum[1].after, after("sum", "InstCombinePass on sum"));
+        assert!(!result["cube"][1].ir_changed);
+    }
+}
+
+/// Whether `ir` is a loop as printed by loop passes, rather than a function.
+pub fn is_loop_ir(ir: &str) -> bool {
+    ir.starts_with("; Preheader:") || ir.starts_with("; Loop:")
+}
+
+/// The label of a basic block, for a line like `loop:   ; preds = %entry`.
+fn block_label(line: &str) -> Option<&str> {
+    if line.starts_with([' ', '\t', ';', '}']) {
+        return None;
+    }
+    let (label, _) = line.split_once(':')?;
+    (!label.is_empty() && (!label.contains(' ') || label.starts_with('"'))).then_some(label)
+}
+
+/// The basic blocks of `ir` by label, each as its label line and instructions.
+fn basic_blocks(ir: &str) -> HashMap<&str, Vec<&str>> {
+    let mut blocks: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut current = None;
+    for line in ir.lines() {
+        if let Some(label) = block_label(line) {
+            blocks.insert(label, vec![line]);
+            current = Some(label);
+        } else if let (Some(label), true) = (current, line.starts_with([' ', '\t'])) {
+            blocks.get_mut(label).unwrap().push(line);
+        } else {
+            current = None;
+        }
+    }
+    blocks
+}
+
+/// Write `ir` with every basic block that has a replacement in `blocks` replaced.
+fn replace_blocks(ir: &str, blocks: &HashMap<&str, Vec<&str>>) -> String {
+    let mut replaced = String::with_capacity(ir.len());
+    let mut skipping = false;
+    for line in ir.lines() {
+        if let Some(block) = block_label(line).and_then(|label| blocks.get(label)) {
+            for line in block {
+                replaced.push_str(line);
+                replaced.push('\n');
+            }
+            skipping = true;
+        } else if !(skipping && line.starts_with([' ', '\t'])) {
+            replaced.push_str(line);
+            replaced.push('\n');
+            skipping = false;
+        }
+    }
+    // Keep the line endings `ir` ends with, like those of split function dumps
+    let ending = &ir[ir.trim_end_matches('\n').len()..];
+    replaced.truncate(replaced.trim_end_matches('\n').len());
+    replaced.push_str(ending);
+    replaced
+}
+
+/// The blocks of `loop_ir` as they are in `function_ir`, i.e. the loop before a
+/// loop pass that only printed it afterwards.
+fn extract_loop(function_ir: &str, loop_ir: &str) -> String {
+    replace_blocks(loop_ir, &basic_blocks(function_ir))
+}
+
+/// `function_ir` with the blocks of the loop in `loop_ir` updated.
+fn merge_loop(function_ir: &str, loop_ir: &str) -> String {
+    replace_blocks(function_ir, &basic_blocks(loop_ir))
+}
+
+/// Extract the function a new pass manager header like `IR Dump After SROAPass on foo`
+/// refers to. Loops are returned with a `%` prefix so that they get attributed to the
+/// function seen before them; module and multi-function CGSCC passes yield `None`.
+fn affected_function_from_header(header: &str) -> Option<String> {
+    let (_, target) = header.rsplit_once(" on ")?;
+    if target == "[module]" {
+        None
+    } else if let Some(scc) = target.strip_prefix('(') {
+        let scc = scc.strip_suffix(')').unwrap_or(scc);
+        (!scc.contains(", ")).then(|| scc.to_string())
+    } else if let Some((_, func)) = target.split_once(" in function ") {
+        Some(func.to_string())
+    } else if target.starts_with("Loop at depth ") || target.starts_with("loop %") {
+        Some("%loop".to_string())
+    } else {
+        Some(target.to_string())
+    }
+}
+
+/// Split a header into its normalized form (`-print-changed` suffixes removed) and
+/// what kind of dump it introduces.
+fn dump_kind(header: &str) -> (String, DumpKind) {
+    if header == "IR Dump At Start" {
+        (header.to_string(), DumpKind::Start)
+    } else if let Some(header) = header.strip_suffix(" omitted because no change") {
+        (header.to_string(), DumpKind::Unchanged)
+    } else if let Some(pass) = header.strip_prefix("IR Deleted After ") {
+        (format!("IR Dump After {}", pass), DumpKind::Deleted)
+    } else if header.starts_with("IR Pass ") || header.ends_with(" filtered out") {
+        (header.to_string(), DumpKind::Ignored)
+    } else if header.starts_with("IR Dump Before ") {
+        (header.to_string(), DumpKind::Before)
+    } else if header.starts_with("IR Dump After ") {
+        (header.to_string(), DumpKind::After)
+    } else {
+        (header.to_string(), DumpKind::Unknown)
+    }
+}
+
+/// Functions a dump without IR of its own belongs to.
+enum Target {
+    Function(String),
+    /// Every function, for module passes
+    All,
+}
+
+/// What splitting the pass dumps of a module keeps track of from one dump to the
+/// next.
+#[derive(Default)]
+struct SplitState {
+    /// Function dumped last, which the loop dumps after it belong to
+    previous_function: Option<String>,
+    /// Functions the module defines, in order, as of the last pass. `None` for
+    /// functions added by a pass, as diffs don't print their define line
+    defined_functions: Option<Vec<Option<String>>>,
+}
+
+impl SplitState {
+    /// The function each `(before, after)` section of a multi-function
+    /// `-print-changed=diff` dump is of. LLVM prints a section for every function
+    /// the module defines, in order, with sections for the functions the pass
+    /// added in between. `None` if the sections don't line up with the functions,
+    /// e.g. when the dump doesn't start with `IR Dump At Start`.
+    fn diff_section_functions(
+        &mut self,
+        sections: &[(String, String)],
+    ) -> Option<Vec<Option<String>>> {
+        let mut defined = self.defined_functions.as_ref()?.iter();
+        let mut functions = Vec::with_capacity(sections.len());
+        for (before, _) in sections {
+            // Added functions have no IR before the pass
+            functions.push(match before.trim().is_empty() {
+                true => None,
+                false => defined.next()?.clone(),
+            });
+        }
+        if defined.next().is_some() {
+            return None;
+        }
+        let remaining = sections
+            .iter()
+            .zip(&functions)
+            .filter(|((_, after), _)| !after.trim().is_empty());
+        self.defined_functions = Some(remaining.map(|(_, name)| name.clone()).collect());
+        Some(functions)
+    }
+}
+
+/// Where a dump goes by its header alone, e.g. `IR Dump After SROAPass on foo`.
+fn header_targets(dump: &PassDump, previous_function: &Option<String>) -> Vec<Target> {
+    match &dump.affected_function {
+        Some(func) if func.starts_with('%') => previous_function
+            .iter()
+            .cloned()
+            .map(Target::Function)
+            .collect(),
+        Some(func) => vec![Target::Function(func.clone())],
+        None => match dump.header.rsplit_once(" on (") {
+            Some((_, scc)) => scc
+                .trim_end_matches(')')
+                .split(", ")
+                .map(|func| Target::Function(func.to_string()))
+                .collect(),
+            None => vec![Target::All],
+        },
+    }
+}
+
+fn unexpected_header(dump: &PassDump) -> PassDumpError {
+    PassDumpError::UnexpectedHeader {
+        line: dump.line,
+        snippet: dump.header.clone(),
+    }
+}
+
+/// The function a dump is of by its header, the first one for SCC passes.
+fn dump_function(dump: &PassDump) -> Option<&str> {
+    match &dump.affected_function {
+        Some(function) => Some(function),
+        None => {
+            let (_, scc) = dump.header.rsplit_once(" on (")?;
+            scc.trim_end_matches(')').split(", ").next()
+        }
+    }
+}
+
+/// Sub-header `-print-changed=diff` puts before each function of a multi-function dump.
+const DIFF_SECTION: &str = "*** IR for function ";
+
+/// Split a `-print-changed=diff` or `=cdiff` body into the IR before and after the
+/// pass. Plain IR always has lines starting in the first column, diffs never do.
+fn split_changed_diff(body: &str) -> Option<(String, String)> {
+    let body = match body.contains('\x1b') {
+        true => Cow::Owned(strip_ansi_colors(body)),
+        false => Cow::Borrowed(body),
+    };
+    let is_diff_line = |line: &str| line.trim().is_empty() || line.starts_with(DIFF_SECTION);
+    if body.lines().all(is_diff_line)
+        || !body
+            .lines()
+            .all(|line| is_diff_line(line) || line.starts_with([' ', '-', '+']))
+    {
+        return None;
+    }
+
+    let mut before = String::with_capacity(body.len());
+    let mut after = String::with_capacity(body.len());
+    for line in body.lines() {
+        let (marker, line) = if line.trim().is_empty() {
+            (' ', "")
+        } else if is_diff_line(line) {
+            (' ', line)
+        } else {
+            (line.as_bytes()[0] as char, &line[1..])
+        };
+        if marker != '+' {
+            before.push_str(line);
+            before.push('\n');
+        }
+        if marker != '-' {
+            after.push_str(line);
+            after.push('\n');
+        }
+    }
+    Some((before, after))
+}
+
+fn strip_ansi_colors(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        stripped.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        let end = rest
+            .find(|c: char| c.is_ascii_alphabetic())
+            .map_or(rest.len(), |end| end + 1);
+        rest = &rest[end..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The sections of a multi-function `-print-changed=diff` dump, one per function.
+/// LLVM names them all after the module or SCC the pass ran on, see
+/// [`SplitState::diff_section_functions`] for which function each one is.
+fn diff_sections(text: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    for line in text.lines() {
+        if line.starts_with(DIFF_SECTION) {
+            sections.push(String::new());
+        } else if let Some(section) = sections.last_mut() {
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+    sections
+}
+
+/// Start of the line each Machine IR function starts with.
+const MACHINE_FUNCTION: &str = "# Machine code for function ";
+
+/// The name of the function `line` starts, if it starts one, or `Some(None)` if
+/// the function has no name.
+fn function_start(line: &str) -> Option<Option<&str>> {
+    let trimmed = line.trim_start();
+    if line.starts_with("define ")
+        || trimmed.starts_with("func.func ")
+        || trimmed.starts_with("tt.func")
+    {
+        let name = line
+            .split_once('@')
+            .and_then(|(_, name)| name.split_once('('));
+        return Some(name.map(|(name, _)| name));
+    }
+    let name = line.strip_prefix(MACHINE_FUNCTION)?;
+    Some(name.split_once(':').map(|(name, _)| name))
+}
+
+#[cfg(test)]
+mod print_changed_tests {
+    use super::tests::{pass_names, process_ok};
+    use super::*;
+
+    #[test]
+    fn print_changed_modes_agree_with_full_dumps() {
+        let options = OptPipelineBackendOptions::default();
+        let full = process_ok(
+            include_bytes!("../tests/fixtures/print-before-after.txt"),
+            &options,
+        );
+        let changed = |passes: &[Pass]| -> Vec<String> {
+            let changed = passes.iter().filter(|pass| pass.ir_changed);
+            changed.map(|pass| pass.name.clone()).collect()
+        };
+        // `=quiet` doesn't print `IR Dump At Start`, so the first change is lost
+        let modes: [(&[u8], bool); 4] = [
+            (
+                include_bytes!("../tests/fixtures/print-changed-cgscc.txt"),
+                true,
+            ),
+            (
+                include_bytes!("../tests/fixtures/print-changed-cgscc-quiet.txt"),
+                false,
+            ),
+            (
+                include_bytes!("../tests/fixtures/print-changed-cgscc-diff.txt"),
+                true,
+            ),
+            (
+                include_bytes!("../tests/fixtures/print-changed-cgscc-cdiff.txt"),
+                true,
+            ),
+        ];
+        for (dump, has_start) in modes {
+            let result = process_ok(dump, &options);
+            for function in ["cube", "sum"] {
+                let (passes, expected) = (&result[function], &full[function]);
+                assert_eq!(passes.last().unwrap().after, expected.last().unwrap().after);
+                if has_start {
+                    assert_eq!(changed(passes), changed(expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn print_changed_diff_sections_of_each_function() {
+        let dump = include_bytes!("../tests/fixtures/print-changed-cgscc-diff.txt");
+        let result = process_ok(dump, &Default::default());
+        let mut functions: Vec<_> = result.keys().map(String::as_str).collect();
+        functions.sort();
+        assert_eq!(functions, ["cube", "square", "sum"]);
+
+        let sum = &result["sum"];
+        let inliner = sum
+            .iter()
+            .find(|pass| pass.name == "InlinerPass on (sum)")
+            .unwrap();
+        assert!(inliner
+            .before
+            .starts_with("define i32 @sum(i32* %v, i32 %n) {\n"));
+        assert!(inliner
+            .before
+            .contains("%sq = call i32 @square(i32 %val)\n"));
+        assert!(inliner.after.contains("%mul.i = mul nsw i32 %val, %val\n"));
+        assert!(!inliner.after.contains("%x"));
+        // The passes after keep the define line and closing brace
+        let last = &sum[sum.len() - 2];
+        assert_eq!(last.name, "InstCombinePass on sum");
+        assert!(last.after.starts_with("define i32 @sum("));
+        assert!(last.after.trim_end().ends_with("\n}"));
+
+        // Deleted after being inlined into its callers
+        let square = &result["square"];
+        let deleted = square.iter().find(|pass| pass.after.is_empty()).unwrap();
+        assert_eq!(deleted.name, "InlinerPass on (sum)");
+        assert!(deleted.before.starts_with("define internal i32 @square("));
+
+        let dead_args = &result["cube"][1];
+        assert_eq!(dead_args.name, "DeadArgumentEliminationPass on [module]");
+        assert!(dead_args.ir_changed);
+        assert!(dead_args.after.contains("call i32 @square(i32 %x)\n"));
+    }
+
+    #[test]
+    fn print_changed_cdiff_matches_diff() {
+        let diff = include_bytes!("../tests/fixtures/print-changed-cgscc-diff.txt");
+        let cdiff = include_bytes!("../tests/fixtures/print-changed-cgscc-cdiff.txt");
+        let options = OptPipelineBackendOptions::default();
+        let (diff, cdiff) = (process_ok(diff, &options), process_ok(cdiff, &options));
+        assert_eq!(diff.len(), cdiff.len());
+        for (function, passes) in &diff {
+            let irs = |passes: &[Pass]| -> Vec<(Arc<str>, Arc<str>)> {
+                passes
+                    .iter()
+                    .map(|pass| (pass.before.clone(), pass.after.clone()))
+                    .collect()
+            };
+            assert_eq!(pass_names(passes), pass_names(&cdiff[function]));
+            assert_eq!(irs(passes), irs(&cdiff[function]));
+        }
+    }
+
+    #[test]
+    fn print_changed_diff_without_start() {
+        let dump = include_bytes!("../tests/fixtures/print-changed-cgscc-diff.txt");
+        let start = memmem::find(dump, b"*** IR Dump After VerifierPass").unwrap();
+        assert!(matches!(
+            process(&dump[start..], &Default::default()),
+            Err(PassDumpError::UnattributedDiff { line: 2, .. })
+        ));
+    }
+}
+
+/// Filter a dump body and collapse runs of blank lines, building it in `scratch`
+/// so that the only allocation is the final body.
+fn clean_body(body: &str, filter: Option<&Regex>, scratch: &mut String) -> Arc<str> {
+    let body = match filter {
+        Some(filter) => filter.replace_all(body, ""),
+        None => Cow::Borrowed(body),
+    };
+    scratch.clear();
+    let mut last_was_blank = true;
+    for line in body.lines() {
+        if line.trim().is_empty() {
+            if !last_was_blank {
+                scratch.push_str(line);
+                scratch.push('\n');
+            }
+            last_was_blank = true;
+        } else {
+            scratch.push_str(line);
+            scratch.push('\n');
+            last_was_blank = false;
+        }
+    }
+    Arc::from(scratch.as_str())
+}
+
+fn passes_match(before: &PassDump, after: &PassDump) -> Result<(), PassDumpError> {
+    assert!(before.header.starts_with("IR Dump Before "));
+    assert!(after.header.starts_with("IR Dump After "));
+    let before = &before.header["IR Dump Before ".len()..];
+    let line = after.line;
+    let mut after = &after.header["IR Dump After ".len()..];
+    if after.ends_with(" (invalidated)") {
+        after = &after[..after.len() - " (invalidated)".len()];
+    }
+
+    if before == after {
+        Ok(())
+    } else {
+        Err(PassDumpError::PassMismatch {
+            line,
+            before_header: before.to_string(),
+            after_header: after.to_string(),
+        })
+    }
+}
+
+/// Whether `dump` interleaves the output of several compilers, by the tag of its
+/// first pass header or by the module IDs it prints.
+pub fn is_interleaved(dump: &[u8]) -> bool {
+    if memmem::find(dump, b"IR Dump ")
+        .and_then(|pos| utf8_lines(&dump[line_start(dump, pos)..]).next())
+        .and_then(split_tag)
+        .is_some()
+    {
+        return true;
+    }
+    let mut module_ids = HashSet::new();
+    for pos in memmem::find_iter(dump, b"; ModuleID = ") {
+        if line_start(dump, pos) != pos {
+            continue;
+        }
+        let module_id = dump[pos..]
+            .split(|&c| c == b'\n')
+            .next()
+            .unwrap_or_default();
+        module_ids.insert(module_id.strip_suffix(b"\r").unwrap_or(module_id));
+        if module_ids.len() > 1 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Start of the line of `dump` that `pos` is in.
+fn line_start(dump: &[u8], pos: usize) -> usize {
+    memchr::memrchr(b'\n', &dump[..pos]).map_or(0, |newline| newline + 1)
+}
+
+/// Whether `dump` was produced with `-print-module-scope`.
+pub fn is_full_module(dump: &[u8]) -> bool {
+    LlvmPassDumpParser::new().is_full_module_dump(dump)
+}
+
+/// The IR of the last dump of the whole module at or before `line`, or else of the
+/// first one after it, as printed by module passes. Without filters, it has the
+/// declarations, attributes and metadata a function needs to stand on its own.
+pub fn module_at(
+    dump: &[u8],
+    options: &OptPipelineBackendOptions,
+    line: usize,
+) -> Result<Option<Arc<str>>, PassDumpError> {
+    let parser = LlvmPassDumpParser::new();
+    let mut reader = PassDumpReader::new(&parser, dump, options)?;
+    let mut module = None;
+    while let Some(dump) = reader.next_dump()? {
+        if dump.line > line && module.is_some() {
+            break;
+        }
+        if dump.lines.starts_with("; ModuleID = ") {
+            module = Some(dump.lines);
+        }
+    }
+    Ok(module)
+}
+
+/// Parse `dump` into the pipeline of every function, along with the compiler
+/// output before the first pass dump and the malformed pass dumps skipped in
+/// lenient mode.
+pub fn process(
+    dump: &[u8],
+    options: &OptPipelineBackendOptions,
+) -> Result<(String, OptPipelineResults, Vec<PassDumpError>), PassDumpError> {
+    let llvm_pass_dump_parser = LlvmPassDumpParser::new();
+    llvm_pass_dump_parser.process(dump, options)
+}
+
+/// The side of the passes a dump doesn't print, e.g. the IR before each pass with
+/// `-print-after-all`. It is reconstructed from the neighbouring passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingIr {
+    Before,
+    After,
+}
+
+/// Which side of the passes `dump` doesn't print, if any.
+pub fn missing_ir(dump: &[u8]) -> Option<MissingIr> {
+    match (
+        memmem::find(dump, b"IR Dump Before").is_some(),
+        memmem::find(dump, b"IR Dump After").is_some(),
+    ) {
+        (false, true) => Some(MissingIr::Before),
+        (true, false) => Some(MissingIr::After),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod missing_ir_tests {
+    use super::tests::{pass_irs, process_ok};
+    use super::*;
+
+    #[test]
+    fn after_only_and_before_only_dumps() {
+        let options = OptPipelineBackendOptions::default();
+        let full = process_ok(
+            include_bytes!("../tests/fixtures/print-before-after.txt"),
+            &options,
+        );
+        let after = include_bytes!("../tests/fixtures/print-after.txt");
+        let before = include_bytes!("../tests/fixtures/print-before.txt");
+        assert_eq!(missing_ir(after), Some(MissingIr::Before));
+        assert_eq!(missing_ir(before), Some(MissingIr::After));
+        for dump in [&after[..], &before[..]] {
+            let result = process_ok(dump, &options);
+            assert_eq!(result.len(), full.len());
+            for (function, passes) in &result {
+                assert_eq!(pass_irs(passes), pass_irs(&full[function]));
+            }
+        }
+    }
+}
+
+/// What [`stream_function`] found in the dump.
+pub struct StreamSummary {
+    /// The function whose passes were streamed, if any matched
+    pub function: Option<String>,
+    /// Whether the dump had any `IR Dump Before` or `IR Dump After`
+    pub has_passes: bool,
+    /// Malformed pass dumps skipped in lenient mode
+    pub warnings: Vec<PassDumpError>,
+}
+
+/// Passes of the selected function, built while the dump is read.
+struct FunctionStream {
+    /// Module and name of the selected function
+    selected: Option<(usize, String)>,
+    /// Name the selected function matched by, qualified with its module if it was
+    /// selected by that
+    selected_name: String,
+    /// Pipeline of the selected function. Until one is selected, module passes are
+    /// gathered for every module
+    pipelines: HashMap<usize, PipelineBuilder>,
+    split_states: HashMap<usize, SplitState>,
+    snapshots: bool,
+    /// Whether the function selected by its name is in several modules, which
+    /// [`process`] tells apart by qualifying the name with the module
+    ambiguous: bool,
+}
+
+impl FunctionStream {
+    fn push(
+        &mut self,
+        parser: &LlvmPassDumpParser,
+        dump: PassDump,
+        full_module: bool,
+        modules: &ModuleTracker,
+        is_selected: &impl Fn(&str) -> bool,
+    ) -> Result<(), PassDumpError> {
+        let module = dump.module;
+        let other_module = self
+            .selected
+            .as_ref()
+            .is_some_and(|(selected, _)| *selected != module);
+        // Other modules only tell whether the function selected by its name is
+        // in them too
+        if other_module && (!self.by_name() || self.ambiguous) {
+            return Ok(());
+        }
+        let mut state = self.split_states.remove(&module).unwrap_or_default();
+        let result = if full_module {
+            self.select(module, FULL_MODULE, modules, is_selected);
+            let dump = parser.attribute_full_dump(dump, &mut state.previous_function);
+            match &dump.affected_function {
+                None if other_module => Ok(()),
+                // Module passes are part of every function's pipeline
+                None => self.pipeline(module).push(dump),
+                Some(func_name) => {
+                    if self.select(module, func_name, modules, is_selected) {
+                        self.pipeline(module).push(dump)?;
+                    }
+                    Ok(())
+                }
+            }
+        } else {
+            parser.split_pass_dump(dump, &mut state, |target, dump| {
+                let name = match (target, &self.selected) {
+                    (Target::Function(name), _) => name,
+                    (Target::All, Some((selected_module, selected)))
+                        if *selected_module == module =>
+                    {
+                        selected.clone()
+                    }
+                    (Target::All, _) => return Ok(()),
+                };
+                if self.select(module, &name, modules, is_selected) {
+                    self.pipeline(module).push(dump)?;
+                }
+                Ok(())
+            })
+        };
+        self.split_states.insert(module, state);
+        result
+    }
+
+    /// Select the function if none is selected yet and it matches, by its name or
+    /// by its name qualified with its module. Returns whether it is selected.
+    fn select(
+        &mut self,
+        module: usize,
+        func_name: &str,
+        modules: &ModuleTracker,
+        is_selected: &impl Fn(&str) -> bool,
+    ) -> bool {
+        if self.selected.is_none() {
+            let qualified_name = modules.qualified_name(func_name, module);
+            if is_selected(func_name) {
+                self.selected_name = func_name.to_string();
+            } else if is_selected(&qualified_name) {
+                self.selected_name = qualified_name;
+            } else {
+                return false;
+            }
+            self.selected = Some((module, func_name.to_string()));
+            self.pipelines
+                .retain(|pipeline_module, _| *pipeline_module == module);
+        }
+        match &self.selected {
+            Some((selected_module, selected)) if selected == func_name => {
+                if *selected_module != module && self.by_name() {
+                    self.ambiguous = true;
+                }
+                *selected_module == module
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the function was selected by its name rather than by its name
+    /// qualified with its module.
+    fn by_name(&self) -> bool {
+        self.selected
+            .as_ref()
+            .is_some_and(|(_, selected)| *selected == self.selected_name)
+    }
+
+    fn pipeline(&mut self, module: usize) -> &mut PipelineBuilder {
+        let snapshots = self.snapshots;
+        self.pipelines
+            .entry(module)
+            .or_insert_with(|| PipelineBuilder::new(snapshots))
+    }
+}
+
+/// Parse a dump incrementally and hand each pass of the first function matching
+/// `is_selected` to `on_pass` as soon as it is complete, so that dumps much larger
+/// than memory can be processed. `on_prefix` gets the compiler output before the
+/// first pass dump and the side of the passes the dump doesn't print, before any
+/// pass.
+///
+/// In interleaved dumps, a function in several modules can only be selected by its
+/// name qualified with its module, like `foo [a.c]`, so the passes of a function
+/// selected by its name are only handed over at the end.
+pub fn stream_function<R: BufRead, E: From<PassDumpError>>(
+    input: R,
+    options: &OptPipelineBackendOptions,
+    is_selected: impl Fn(&str) -> bool,
+    on_prefix: impl FnOnce(&str, Option<MissingIr>) -> Result<(), E>,
+    mut on_pass: impl FnMut(&str, Pass) -> Result<(), E>,
+) -> Result<StreamSummary, E> {
+    let parser = LlvmPassDumpParser::new();
+    let input = ReadLines {
+        input,
+        line: String::new(),
+    };
+    let mut reader = PassDumpReader::new(&parser, input, options)?;
+    let mut on_prefix = Some(on_prefix);
+    let mut stream = FunctionStream {
+        selected: None,
+        selected_name: String::new(),
+        pipelines: HashMap::new(),
+        split_states: HashMap::new(),
+        snapshots: options.snapshots,
+        ambiguous: false,
+    };
+    // Pass dumps read before it is known whether they print the full module
+    let mut undecided = Vec::new();
+
+    while let Some(dump) = reader.next_dump()? {
+        let full_module = match options.full_module {
+            true => Some(true),
+            false => reader.full_module,
+        };
+        let Some(full_module) = full_module else {
+            undecided.push(dump);
+            continue;
+        };
+        for dump in undecided.drain(..).chain([dump]) {
+            let pushed = stream.push(&parser, dump, full_module, &reader.modules, &is_selected);
+            reader.diagnostics.recover(pushed)?;
+        }
+        // Hold passes back until it is known which side of them is reconstructed
+        let Some(missing) = reader.missing_ir(false) else {
+            continue;
+        };
+        if let Some(on_prefix) = on_prefix.take() {
+            on_prefix(&reader.prefix, missing)?;
+        }
+        // Until the end, a function of interleaved modules selected by its name may
+        // turn out to be in another module as well
+        if reader.interleaved() && stream.by_name() {
+            continue;
+        }
+        if let Some((module, _)) = &stream.selected {
+            let pipeline = stream.pipelines.get_mut(module);
+            for pass in pipeline
+                .into_iter()
+                .flat_map(|pipeline| pipeline.done.drain(..))
+            {
+                on_pass(&stream.selected_name, pass)?;
+            }
+        }
+    }
+    for dump in undecided {
+        let pushed = stream.push(&parser, dump, false, &reader.modules, &is_selected);
+        reader.diagnostics.recover(pushed)?;
+    }
+
+    // Like `process`, which only knows it by its name qualified with its module
+    if stream.ambiguous {
+        stream.selected = None;
+    }
+
+    let missing = reader.missing_ir(true);
+    if let Some(missing) = missing {
+        if let Some(on_prefix) = on_prefix.take() {
+            on_prefix(&reader.prefix, missing)?;
+        }
+        if let Some((module, _)) = &stream.selected {
+            let unfinished_pass = reader.unfinished_pass(*module);
+            let pipeline = stream.pipelines.remove(module);
+            for pass in pipeline
+                .into_iter()
+                .flat_map(|pipeline| pipeline.finish(unfinished_pass))
+            {
+                on_pass(&stream.selected_name, pass)?;
+            }
+        }
+    }
+    Ok(StreamSummary {
+        function: stream.selected.map(|_| stream.selected_name),
+        has_passes: missing.is_some(),
+        warnings: reader.diagnostics.warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::io::{BufReader, Read};
+
+    pub(super) fn process_ok(
+        dump: &[u8],
+        options: &OptPipelineBackendOptions,
+    ) -> OptPipelineResults {
+        let (_, result, warnings) = process(dump, options).unwrap();
+        assert!(warnings.is_empty());
+        result
+    }
+
+    pub(super) fn pass_names(passes: &[Pass]) -> Vec<&str> {
+        passes.iter().map(|pass| pass.name.as_str()).collect()
+    }
+
+    /// The name and the IR before and after each pass.
+    pub(super) fn pass_irs(passes: &[Pass]) -> Vec<(&str, &str, &str)> {
+        passes
+            .iter()
+            .map(|pass| (pass.name.as_str(), &*pass.before, &*pass.after))
+            .collect()
+    }
+
+    /// The passes `stream_function` hands over for `function`.
+    pub(super) fn stream_ok(
+        dump: &[u8],
+        options: &OptPipelineBackendOptions,
+        function: &str,
+    ) -> Vec<Pass> {
+        let mut passes = Vec::new();
+        let summary = stream_function::<_, PassDumpError>(
+            dump,
+            options,
+            |name| name == function,
+            |_, _| Ok(()),
+            |_, pass| {
+                passes.push(pass);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(summary.function.as_deref(), Some(function));
+        assert!(summary.warnings.is_empty());
+        passes
+    }
+
+    #[test]
+    fn streaming_matches_parsing_the_whole_dump() {
+        for dump in [
+            &include_bytes!("../tests/fixtures/print-before-after.txt")[..],
+            include_bytes!("../tests/fixtures/interleaved-tagged.txt"),
+            include_bytes!("../tests/fixtures/interleaved-module-scope.txt"),
+        ] {
+            let options = OptPipelineBackendOptions {
+                full_module: is_full_module(dump),
+                ..Default::default()
+            };
+            let result = process_ok(dump, &options);
+            for (function, passes) in &result {
+                let streamed = stream_ok(dump, &options, function);
+                assert_eq!(pass_irs(&streamed), pass_irs(passes));
+                // Functions in several modules are only known by their qualified name
+                if let Some((name, _)) = function.split_once(" [") {
+                    let summary = stream_function::<_, PassDumpError>(
+                        dump,
+                        &options,
+                        |func_name| func_name == name,
+                        |_, _| Ok(()),
+                        |_, _| panic!("{} is in several modules", name),
+                    )
+                    .unwrap();
+                    assert_eq!(summary.function, None);
+                }
+            }
+        }
+
+        let dump = include_bytes!("../tests/fixtures/print-before-after.txt");
+        let result = process_ok(dump, &OptPipelineBackendOptions::default());
+        let mut functions: Vec<_> = result.keys().map(String::as_str).collect();
+        functions.sort();
+        assert_eq!(functions, ["cube", "square", "sum"]);
+        assert_eq!(
+            pass_names(&result["cube"]),
+            [
+                "VerifierPass on [module]",
+                "DeadArgumentEliminationPass on [module]",
+                "InlinerPass on (cube)",
+                "InstCombinePass on cube",
+                "VerifierPass on [module]",
+            ]
+        );
+    }
+
+    /// Reads `dump` and counts the bytes read.
+    struct CountingReader<'a> {
+        dump: &'a [u8],
+        read: &'a Cell<usize>,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = (&self.dump[self.read.get()..]).read(buf)?;
+            self.read.set(self.read.get() + read);
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn legacy_dumps_stream_before_the_end() {
+        let dump = include_bytes!("../tests/fixtures/legacy-print-after-all.txt");
+        let options = OptPipelineBackendOptions::default();
+        let result = process_ok(dump, &options);
+        let read = Cell::new(0);
+        let input = BufReader::with_capacity(64, CountingReader { dump, read: &read });
+        // How much of the dump was read when each pass was handed over
+        let mut passes = Vec::new();
+        let mut read_at = Vec::new();
+        stream_function::<_, PassDumpError>(
+            input,
+            &options,
+            |name| name == "sum",
+            |_, _| Ok(()),
+            |_, pass| {
+                passes.push(pass);
+                read_at.push(read.get());
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(pass_irs(&passes), pass_irs(&result["sum"]));
+        assert!(read_at[0] < dump.len() / 2);
+    }
+
+    #[test]
+    fn user_line_rules_remove_lines_with_a_match() {
+        let options = OptPipelineBackendOptions {
+            line_filters: vec!["call void @trace".to_string(), ":keep".to_string()],
+            ..Default::default()
+        };
+        let filter = LlvmPassDumpParser::new().ir_filter(&options).unwrap();
+        let body = "entry:\n  call void @trace(i32 1)\n  keep\n  ret void\n";
+        assert_eq!(filter.replace_all(body, ""), "entry:\n  keep\n  ret void\n");
+    }
+
+    #[test]
+    fn full_module_invalidated_loop_pass() {
+        let dump = include_bytes!("../tests/fixtures/full-module-invalidated.txt");
+        assert!(is_full_module(dump));
+        let options = OptPipelineBackendOptions {
+            full_module: true,
+            ..Default::default()
+        };
+        let result = process_ok(dump, &options);
+        let sum = &result["sum"];
+        assert_eq!(
+            pass_names(sum)[3],
+            "LoopDeletionPass on Loop at depth 1 containing: %loop<header><latch><exiting> (sum)"
+        );
+        assert!(sum[3].ir_changed);
+        assert_eq!(pass_names(&result[FULL_MODULE]).len(), 3);
+
+        let streamed = stream_ok(dump, &options, "sum");
+        assert_eq!(pass_names(&streamed), pass_names(sum));
+    }
+
+    #[test]
+    fn invalid_utf8_reports_its_line() {
+        let dump = b"*** IR Dump After InstCombinePass on f ***\ndefine void @f() {\n  ; \xff\n}\n";
+        let options = OptPipelineBackendOptions::default();
+        assert!(matches!(
+            process(dump, &options),
+            Err(PassDumpError::InvalidUtf8 { line: 3 })
+        ));
+        let streamed = stream_function::<_, PassDumpError>(
+            &dump[..],
+            &options,
+            |_| true,
+            |_, _| Ok(()),
+            |_, _| Ok(()),
+        );
+        assert!(streamed.is_err());
+    }
 }
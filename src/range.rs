@@ -0,0 +1,272 @@
+//! Stretches of a function's pipeline selected with `--from` and `--to`.
+
+use crate::optpipeline::Pass;
+use std::{fmt, str::FromStr};
+
+/// A pass of a pipeline: its 1-based index, as in diff titles, or its name with
+/// the occurrence to pick, like `InstCombinePass#3`.
+#[derive(Clone, Debug)]
+pub enum PassSelector {
+    Index(usize),
+    Name { name: String, occurrence: usize },
+}
+
+impl FromStr for PassSelector {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Ok(index) = text.parse::<usize>() {
+            return match index {
+                0 => Err("passes are numbered from 1".to_string()),
+                _ => Ok(PassSelector::Index(index)),
+            };
+        }
+        let (name, occurrence) = match text.rsplit_once('#') {
+            Some((name, occurrence)) => match occurrence.parse::<usize>() {
+                Ok(occurrence) if occurrence > 0 => (name, occurrence),
+                _ => {
+                    return Err(format!(
+                        "invalid occurrence '{}', expected a number from 1",
+                        occurrence
+                    ))
+                }
+            },
+            None => (text, 1),
+        };
+        if name.is_empty() {
+            return Err("expected a pass index or name".to_string());
+        }
+        Ok(PassSelector::Name {
+            name: name.to_string(),
+            occurrence,
+        })
+    }
+}
+
+impl fmt::Display for PassSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PassSelector::Index(index) => write!(f, "{}", index),
+            PassSelector::Name {
+                name,
+                occurrence: 1,
+            } => write!(f, "{}", name),
+            PassSelector::Name { name, occurrence } => write!(f, "{}#{}", name, occurrence),
+        }
+    }
+}
+
+impl PassSelector {
+    /// Whether `pass`, at 0-based position `i`, is the selected one. `seen` counts
+    /// the passes with the selected name so far.
+    fn matches(&self, i: usize, pass: &Pass, seen: &mut usize) -> bool {
+        match self {
+            PassSelector::Index(index) => i + 1 == *index,
+            PassSelector::Name { name, occurrence } => {
+                // `InstCombinePass` stands for `InstCombinePass on foo` as well
+                let matches_name = pass
+                    .name
+                    .strip_prefix(name.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '));
+                if !matches_name {
+                    return false;
+                }
+                *seen += 1;
+                *seen == *occurrence
+            }
+        }
+    }
+}
+
+/// Tracks whether the passes of a pipeline are between `--from` and `--to`, both
+/// included. Passes have to be given in pipeline order, so that streamed dumps
+/// can be sliced as they are read.
+pub struct PassRange<'a> {
+    from: Option<&'a PassSelector>,
+    to: Option<&'a PassSelector>,
+    from_seen: usize,
+    to_seen: usize,
+    started: bool,
+    ended: bool,
+}
+
+impl<'a> PassRange<'a> {
+    pub fn new(from: Option<&'a PassSelector>, to: Option<&'a PassSelector>) -> Self {
+        PassRange {
+            from,
+            to,
+            from_seen: 0,
+            to_seen: 0,
+            started: from.is_none(),
+            ended: false,
+        }
+    }
+
+    /// Whether `pass`, at 0-based position `i` in its pipeline, is in the range.
+    pub fn contains(&mut self, i: usize, pass: &Pass) -> bool {
+        if let Some(from) = self.from {
+            self.started |= !self.started && from.matches(i, pass, &mut self.from_seen);
+        }
+        let inside = self.started && !self.ended;
+        if let Some(to) = self.to {
+            self.ended |= !self.ended && to.matches(i, pass, &mut self.to_seen);
+        }
+        inside
+    }
+
+    /// The option, and its selector, that didn't match any of the passes given so
+    /// far.
+    pub fn missing(&self) -> Option<(&'static str, &'a PassSelector)> {
+        match (self.from, self.to) {
+            (Some(from), _) if !self.started => Some(("--from", from)),
+            (_, Some(to)) if !self.ended => Some(("--to", to)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selector(text: &str) -> PassSelector {
+        text.parse().unwrap()
+    }
+
+    fn pass(name: &str) -> Pass {
+        Pass {
+            name: name.to_string(),
+            machine: false,
+            after: "".into(),
+            before: "".into(),
+            ir_changed: true,
+            crashed: false,
+            line: 0,
+        }
+    }
+
+    /// The indices of `names` in the range.
+    fn selected(from: Option<&str>, to: Option<&str>, names: &[&str]) -> Vec<usize> {
+        let (from, to) = (from.map(selector), to.map(selector));
+        let mut range = PassRange::new(from.as_ref(), to.as_ref());
+        (0..names.len())
+            .filter(|&i| range.contains(i, &pass(names[i])))
+            .collect()
+    }
+
+    #[test]
+    fn parses_selectors() {
+        assert!(matches!(selector("3"), PassSelector::Index(3)));
+        assert!(matches!(
+            selector("InstCombinePass"),
+            PassSelector::Name { ref name, occurrence: 1 } if name == "InstCombinePass"
+        ));
+        assert!(matches!(
+            selector("InstCombinePass#3"),
+            PassSelector::Name { ref name, occurrence: 3 } if name == "InstCombinePass"
+        ));
+        // Only the last `#` separates the occurrence
+        assert!(matches!(
+            selector("A#B#2"),
+            PassSelector::Name { ref name, occurrence: 2 } if name == "A#B"
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_selectors() {
+        for (text, error) in [
+            ("0", "passes are numbered from 1"),
+            ("", "expected a pass index or name"),
+            ("#2", "expected a pass index or name"),
+            (
+                "InstCombinePass#0",
+                "invalid occurrence '0', expected a number from 1",
+            ),
+            (
+                "InstCombinePass#abc",
+                "invalid occurrence 'abc', expected a number from 1",
+            ),
+            (
+                "InstCombinePass#",
+                "invalid occurrence '', expected a number from 1",
+            ),
+        ] {
+            assert_eq!(text.parse::<PassSelector>().unwrap_err(), error, "{text}");
+        }
+    }
+
+    #[test]
+    fn displays_selectors_as_parsed() {
+        for text in ["3", "InstCombinePass", "InstCombinePass#3"] {
+            assert_eq!(selector(text).to_string(), text);
+        }
+        assert_eq!(selector("InstCombinePass#1").to_string(), "InstCombinePass");
+    }
+
+    const PIPELINE: &[&str] = &[
+        "SROAPass on foo",
+        "InstCombinePass on foo",
+        "SimplifyCFGPass on foo",
+        "InstCombinePass on foo",
+        "InstCombinePassManager on foo",
+        "GVNPass on foo",
+    ];
+
+    #[test]
+    fn includes_both_ends() {
+        assert_eq!(selected(Some("2"), Some("4"), PIPELINE), [1, 2, 3]);
+        assert_eq!(selected(Some("3"), Some("3"), PIPELINE), [2]);
+        assert_eq!(selected(None, Some("2"), PIPELINE), [0, 1]);
+        assert_eq!(selected(Some("5"), None, PIPELINE), [4, 5]);
+        assert_eq!(selected(None, None, PIPELINE), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn counts_occurrences_of_names() {
+        assert_eq!(
+            selected(Some("InstCombinePass"), Some("SimplifyCFGPass"), PIPELINE),
+            [1, 2]
+        );
+        // `InstCombinePassManager` isn't an occurrence of `InstCombinePass`
+        assert_eq!(
+            selected(Some("InstCombinePass#2"), None, PIPELINE),
+            [3, 4, 5]
+        );
+        assert_eq!(
+            selected(Some("SROAPass"), Some("InstCombinePass#2"), PIPELINE),
+            [0, 1, 2, 3]
+        );
+        assert!(selected(Some("InstCombinePass#3"), None, PIPELINE).is_empty());
+    }
+
+    #[test]
+    fn reports_unmatched_selectors() {
+        let from = selector("InstCombinePass#3");
+        let to = selector("GVNPass");
+        let mut range = PassRange::new(Some(&from), Some(&to));
+        assert!(matches!(range.missing(), Some(("--from", _))));
+        for (i, name) in PIPELINE.iter().enumerate() {
+            range.contains(i, &pass(name));
+        }
+        let (option, missing) = range.missing().unwrap();
+        assert_eq!((option, missing.to_string()), ("--from", from.to_string()));
+
+        let from = selector("2");
+        let to = selector("LICMPass");
+        let mut range = PassRange::new(Some(&from), Some(&to));
+        for (i, name) in PIPELINE.iter().enumerate() {
+            range.contains(i, &pass(name));
+        }
+        let (option, missing) = range.missing().unwrap();
+        assert_eq!(
+            (option, missing.to_string()),
+            ("--to", "LICMPass".to_string())
+        );
+
+        let mut range = PassRange::new(Some(&from), None);
+        for (i, name) in PIPELINE.iter().enumerate() {
+            range.contains(i, &pass(name));
+        }
+        assert!(range.missing().is_none());
+    }
+}
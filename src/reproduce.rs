@@ -0,0 +1,586 @@
+//! Standalone `.ll` files for reproducing a pass with `opt`, for `--crash-ir` and
+//! `--extract`.
+
+use crate::optpipeline::{self, Pass};
+use itertools::Itertools;
+use memchr::memchr;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use thiserror::Error;
+
+/// `opt -passes` names of the new pass manager's passes, by the class name they
+/// are dumped with. Loop passes that need MemorySSA are wrapped in `loop-mssa`.
+const OPT_PASS_NAMES: &[(&str, &str)] = &[
+    // Module passes
+    ("AlwaysInlinerPass", "always-inline"),
+    ("Annotation2MetadataPass", "annotation2metadata"),
+    ("AttributorPass", "attributor"),
+    ("CalledValuePropagationPass", "called-value-propagation"),
+    ("CGProfilePass", "cg-profile"),
+    ("ConstantMergePass", "constmerge"),
+    ("CoroCleanupPass", "coro-cleanup"),
+    ("CoroEarlyPass", "coro-early"),
+    ("CrossDSOCFIPass", "cross-dso-cfi"),
+    ("DeadArgumentEliminationPass", "deadargelim"),
+    ("EliminateAvailableExternallyPass", "elim-avail-extern"),
+    ("ForceFunctionAttrsPass", "forceattrs"),
+    ("GlobalDCEPass", "globaldce"),
+    ("GlobalOptPass", "globalopt"),
+    ("GlobalSplitPass", "globalsplit"),
+    ("HotColdSplittingPass", "hotcoldsplit"),
+    ("InferFunctionAttrsPass", "inferattrs"),
+    ("IPSCCPPass", "ipsccp"),
+    ("LowerTypeTestsPass", "lower-type-tests"),
+    ("MergeFunctionsPass", "mergefunc"),
+    ("ModuleInlinerWrapperPass", "inliner-wrapper"),
+    ("OpenMPOptPass", "openmp-opt"),
+    ("PartialInlinerPass", "partial-inliner"),
+    ("RecomputeGlobalsAAPass", "recompute-globalsaa"),
+    ("RelLookupTableConverterPass", "rel-lookup-table-converter"),
+    ("ReversePostOrderFunctionAttrsPass", "rpo-function-attrs"),
+    ("StripDeadPrototypesPass", "strip-dead-prototypes"),
+    ("WholeProgramDevirtPass", "wholeprogramdevirt"),
+    // CGSCC passes
+    ("ArgumentPromotionPass", "argpromotion"),
+    ("AttributorCGSCCPass", "attributor-cgscc"),
+    ("CoroSplitPass", "coro-split"),
+    ("InlinerPass", "inline"),
+    ("OpenMPOptCGSCCPass", "openmp-opt-cgscc"),
+    ("PostOrderFunctionAttrsPass", "function-attrs"),
+    // Function passes
+    ("ADCEPass", "adce"),
+    ("AggressiveInstCombinePass", "aggressive-instcombine"),
+    ("AlignmentFromAssumptionsPass", "alignment-from-assumptions"),
+    ("AnnotationRemarksPass", "annotation-remarks"),
+    ("BDCEPass", "bdce"),
+    ("CallSiteSplittingPass", "callsite-splitting"),
+    ("ConstraintEliminationPass", "constraint-elimination"),
+    ("CoroElidePass", "coro-elide"),
+    ("CorrelatedValuePropagationPass", "correlated-propagation"),
+    ("DCEPass", "dce"),
+    ("DivRemPairsPass", "div-rem-pairs"),
+    ("DSEPass", "dse"),
+    ("EarlyCSEPass", "early-cse<memssa>"),
+    ("EntryExitInstrumenterPass", "ee-instrument"),
+    ("Float2IntPass", "float2int"),
+    ("GVNHoistPass", "gvn-hoist"),
+    ("GVNPass", "gvn"),
+    ("GVNSinkPass", "gvn-sink"),
+    ("InferAddressSpacesPass", "infer-address-spaces"),
+    ("InjectTLIMappings", "inject-tli-mappings"),
+    ("InstCombinePass", "instcombine"),
+    ("InstSimplifyPass", "instsimplify"),
+    ("JumpThreadingPass", "jump-threading"),
+    ("LCSSAPass", "lcssa"),
+    ("LibCallsShrinkWrapPass", "libcalls-shrinkwrap"),
+    ("LoopDataPrefetchPass", "loop-data-prefetch"),
+    ("LoopDistributePass", "loop-distribute"),
+    ("LoopFusePass", "loop-fusion"),
+    ("LoopLoadEliminationPass", "loop-load-elim"),
+    ("LoopSimplifyPass", "loop-simplify"),
+    ("LoopSinkPass", "loop-sink"),
+    ("LoopUnrollPass", "loop-unroll"),
+    ("LoopVectorizePass", "loop-vectorize"),
+    ("LoopVersioningPass", "loop-versioning"),
+    ("LowerAtomicPass", "lower-atomic"),
+    ("LowerConstantIntrinsicsPass", "lower-constant-intrinsics"),
+    ("LowerExpectIntrinsicPass", "lower-expect"),
+    ("LowerInvokePass", "lower-invoke"),
+    ("LowerMatrixIntrinsicsPass", "lower-matrix-intrinsics"),
+    ("LowerSwitchPass", "lower-switch"),
+    ("MemCpyOptPass", "memcpyopt"),
+    ("MergedLoadStoreMotionPass", "mldst-motion"),
+    ("MergeICmpsPass", "mergeicmps"),
+    ("NaryReassociatePass", "nary-reassociate"),
+    ("NewGVNPass", "newgvn"),
+    ("PartiallyInlineLibCallsPass", "partially-inline-libcalls"),
+    ("PromotePass", "mem2reg"),
+    ("ReassociatePass", "reassociate"),
+    ("ScalarizerPass", "scalarizer"),
+    ("SCCPPass", "sccp"),
+    (
+        "SeparateConstOffsetFromGEPPass",
+        "separate-const-offset-from-gep",
+    ),
+    ("SimplifyCFGPass", "simplifycfg"),
+    ("SinkingPass", "sink"),
+    ("SLPVectorizerPass", "slp-vectorizer"),
+    ("SpeculativeExecutionPass", "speculative-execution"),
+    ("SROAPass", "sroa"),
+    ("StraightLineStrengthReducePass", "slsr"),
+    ("TailCallElimPass", "tailcallelim"),
+    ("VectorCombinePass", "vector-combine"),
+    ("WarnMissedTransformationsPass", "transform-warning"),
+    // Loop passes
+    ("CanonicalizeFreezeInLoopsPass", "loop(canon-freeze)"),
+    ("GuardWideningPass", "loop(guard-widening)"),
+    ("IndVarSimplifyPass", "loop(indvars)"),
+    ("IRCEPass", "irce"),
+    ("LICMPass", "loop-mssa(licm)"),
+    ("LNICMPass", "loop-mssa(lnicm)"),
+    ("LoopBoundSplitPass", "loop(loop-bound-split)"),
+    ("LoopDeletionPass", "loop(loop-deletion)"),
+    ("LoopFlattenPass", "loop(loop-flatten)"),
+    ("LoopFullUnrollPass", "loop(loop-unroll-full)"),
+    ("LoopIdiomRecognizePass", "loop(loop-idiom)"),
+    ("LoopInstSimplifyPass", "loop(loop-instsimplify)"),
+    ("LoopInterchangePass", "loop(loop-interchange)"),
+    ("LoopPredicationPass", "loop(loop-predication)"),
+    ("LoopRotatePass", "loop(loop-rotate)"),
+    ("LoopSimplifyCFGPass", "loop(loop-simplifycfg)"),
+    ("LoopStrengthReducePass", "loop(loop-reduce)"),
+    ("LoopUnrollAndJamPass", "loop(loop-unroll-and-jam)"),
+    ("LoopVersioningLICMPass", "loop(loop-versioning-licm)"),
+    ("SimpleLoopUnswitchPass", "loop-mssa(simple-loop-unswitch)"),
+];
+
+/// Linkage types that only definitions can have.
+const DEFINITION_LINKAGES: &[&str] = &[
+    "private",
+    "internal",
+    "available_externally",
+    "linkonce",
+    "linkonce_odr",
+    "weak",
+    "weak_odr",
+    "common",
+    "appending",
+    "external",
+];
+
+#[derive(Debug, Error)]
+pub enum ReproduceError {
+    #[error("The dump doesn't end in the middle of a pass, so there is no crash to reproduce")]
+    NoCrash,
+    #[error("`{0}` ran on Machine IR, which can't be read back from a dump. Use `llc -stop-before=<pass>` to get MIR for `llc -run-pass=<pass>` instead")]
+    MachineIr(String),
+    #[error("`{0}` is a loop pass, which only dumps the loop. Add `-mllvm -print-module-scope` to dump the whole module")]
+    LoopIr(String),
+}
+
+/// A module to feed to `opt` to run a pass on it again.
+pub struct Reproducer<'a> {
+    /// The pass as it is named in the dump
+    pub pass: &'a str,
+    pub ir: String,
+    /// Globals, functions, attribute groups and metadata the IR uses that the dump
+    /// doesn't define, so `opt` can't read it as is
+    pub unresolved: Vec<String>,
+}
+
+impl Reproducer<'_> {
+    /// The `-passes` pipeline that runs the pass, if it is known.
+    pub fn opt_passes(&self) -> Option<&'static str> {
+        let class_name = self
+            .pass
+            .split_once(" on ")
+            .map_or(self.pass, |(name, _)| name);
+        OPT_PASS_NAMES
+            .iter()
+            .find(|(name, _)| *name == class_name)
+            .map(|(_, opt_name)| *opt_name)
+    }
+}
+
+/// The pass the dump ends in and the IR given to it, from the unfiltered
+/// pipelines of the selected functions. A module pass is part of every function's
+/// pipeline, so its IR is put together from all of them.
+pub fn crashed_ir<'a>(pipelines: &[&'a [Pass]]) -> Result<(&'a Pass, String), ReproduceError> {
+    let crashed: Vec<&Pass> = pipelines
+        .iter()
+        .filter_map(|pipeline| pipeline.last().filter(|pass| pass.crashed))
+        .collect();
+    let Some(first) = crashed.first() else {
+        return Err(ReproduceError::NoCrash);
+    };
+    if let Some(module) = crashed.iter().find(|pass| is_module(&pass.before)) {
+        return Ok((module, module.before.to_string()));
+    }
+    let ir = crashed
+        .iter()
+        .map(|pass| pass.before.trim_end())
+        .join("\n\n");
+    Ok((first, ir + "\n"))
+}
+
+/// Make `ir`, the unfiltered IR before or after `pass`, into a module `opt` can
+/// read. Unless it already is the whole module, the globals and declarations it
+/// uses, attributes and metadata are taken from `context`, a dump of the whole
+/// module from around the same time, see [`optpipeline::module_at`].
+pub fn reproducer<'a>(
+    pass: &'a Pass,
+    ir: &str,
+    context: Option<&str>,
+) -> Result<Reproducer<'a>, ReproduceError> {
+    if pass.machine {
+        return Err(ReproduceError::MachineIr(pass.name.clone()));
+    }
+    if optpipeline::is_loop_ir(ir) {
+        return Err(ReproduceError::LoopIr(pass.name.clone()));
+    }
+    let (ir, unresolved) = match context {
+        _ if is_module(ir) => (ir.to_string(), Vec::new()),
+        Some(context) => Module::parse(context).complete(ir),
+        None => Module::default().complete(ir),
+    };
+    Ok(Reproducer {
+        pass: &pass.name,
+        ir,
+        unresolved,
+    })
+}
+
+fn is_module(ir: &str) -> bool {
+    ir.starts_with("; ModuleID = ")
+}
+
+/// The top-level entities of a module, as far as a function taken out of it needs
+/// them.
+#[derive(Default)]
+struct Module<'a> {
+    /// Source file name, target, types, comdats and module asm, kept as they are
+    header: Vec<&'a str>,
+    /// Global variables, aliases and ifuncs, and a declaration of every function,
+    /// in module order
+    entities: Vec<(&'a str, Cow<'a, str>)>,
+    by_name: HashMap<&'a str, usize>,
+    /// Attribute groups and metadata, kept as they are
+    trailer: Vec<&'a str>,
+}
+
+impl<'a> Module<'a> {
+    fn parse(module: &'a str) -> Self {
+        let mut parsed = Module::default();
+        let mut in_function = false;
+        for line in module.lines() {
+            if in_function {
+                in_function = line != "}";
+                continue;
+            }
+            if line.trim().is_empty() || line.starts_with(';') || line.starts_with("uselistorder") {
+                continue;
+            }
+            let entity = if let Some(define) = line.strip_prefix("define ") {
+                in_function = line.ends_with('{');
+                declaration(define).map(Cow::Owned)
+            } else if line.starts_with("declare ") || line.starts_with('@') {
+                Some(Cow::Borrowed(line))
+            } else if line.starts_with("attributes ") || line.starts_with('!') {
+                parsed.trailer.push(line);
+                None
+            } else {
+                parsed.header.push(line);
+                None
+            };
+            let Some(entity) = entity else {
+                continue;
+            };
+            if let Some(name) = references(line, b'@').next() {
+                parsed.by_name.insert(name, parsed.entities.len());
+                parsed.entities.push((name, entity));
+            }
+        }
+        parsed
+    }
+
+    /// Put the functions in `ir` into the module, with everything they use.
+    /// Returns the module and the names of what isn't in it.
+    fn complete(&self, ir: &str) -> (String, Vec<String>) {
+        let mut seen = defined_functions(ir);
+        let mut pending: Vec<&str> = references(ir, b'@')
+            .chain(self.trailer.iter().flat_map(|line| references(line, b'@')))
+            .collect();
+        let mut used = BTreeSet::new();
+        let mut unresolved = BTreeSet::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name) {
+                continue;
+            }
+            match self.by_name.get(name) {
+                Some(&index) => {
+                    used.insert(index);
+                    pending.extend(references(&self.entities[index].1, b'@'));
+                }
+                None => {
+                    unresolved.insert(name.to_string());
+                }
+            }
+        }
+        let (globals, declarations): (Vec<&str>, Vec<&str>) = used
+            .into_iter()
+            .map(|index| &*self.entities[index].1)
+            .partition(|entity| entity.starts_with('@'));
+
+        // Attribute groups are numbered for the whole module, so functions can use
+        // groups added after the module was dumped. Their `; Function Attrs:`
+        // comment has all but the string attributes
+        let defined: HashSet<&str> = self
+            .trailer
+            .iter()
+            .filter_map(|line| {
+                let definition = line.strip_prefix("attributes ").unwrap_or(line);
+                definition.split_once(" = ").map(|(name, _)| name)
+            })
+            .collect();
+        let commented = commented_attributes(ir);
+        let mut trailer = self
+            .trailer
+            .iter()
+            .map(|line| line.to_string())
+            .collect_vec();
+        for group in references(ir, b'#')
+            .chain(declarations.iter().flat_map(|line| references(line, b'#')))
+            .chain(references(ir, b'!'))
+            .unique()
+        {
+            if defined.contains(group) {
+                continue;
+            }
+            match commented.get(group) {
+                Some(attributes) => {
+                    trailer.push(format!("attributes {group} = {{ {attributes} }}"))
+                }
+                None => {
+                    unresolved.insert(group.to_string());
+                }
+            }
+        }
+
+        let sections = [
+            self.header.join("\n"),
+            globals.join("\n"),
+            ir.trim_end().to_string(),
+            declarations.join("\n"),
+            trailer.join("\n"),
+        ];
+        let module = sections
+            .iter()
+            .filter(|section| !section.is_empty())
+            .join("\n\n");
+        (module + "\n", unresolved.into_iter().collect())
+    }
+}
+
+/// Attributes of the functions in `ir` by their group, from the `; Function Attrs:`
+/// comment before their `define`.
+fn commented_attributes(ir: &str) -> HashMap<&str, &str> {
+    let mut groups = HashMap::new();
+    let mut attributes = None;
+    for line in ir.lines() {
+        if let Some(comment) = line.strip_prefix("; Function Attrs: ") {
+            attributes = Some(comment.trim());
+        } else if let Some(define) = line.strip_prefix("define ") {
+            if let (Some(attributes), Some(group)) =
+                (attributes.take(), references(define, b'#').next())
+            {
+                groups.insert(group, attributes);
+            }
+        } else {
+            attributes = None;
+        }
+    }
+    groups
+}
+
+/// A declaration for the function defined by `define` (the line without the
+/// `define `), with the linkage, body and everything else only a definition can
+/// have left out.
+fn declaration(define: &str) -> Option<String> {
+    let mut rest = define;
+    while let Some((word, tail)) = rest.split_once(' ') {
+        if !DEFINITION_LINKAGES.contains(&word) {
+            break;
+        }
+        rest = tail;
+    }
+    // The signature ends with the parenthesis that closes the parameter list
+    let name = rest.find('@')?;
+    let params = name + rest[name..].find('(')?;
+    let mut depth = 0;
+    let end = rest[params..].char_indices().find_map(|(i, c)| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        (depth == 0).then_some(params + i + 1)
+    })?;
+    let attributes = rest[end..].split_whitespace().filter(|word| {
+        matches!(*word, "unnamed_addr" | "local_unnamed_addr")
+            || word.starts_with("addrspace(")
+            || word.strip_prefix('#').is_some_and(|group| {
+                !group.is_empty() && group.bytes().all(|byte| byte.is_ascii_digit())
+            })
+    });
+    Some(
+        std::iter::once(format!("declare {}", &rest[..end]))
+            .chain(attributes.map(str::to_string))
+            .join(" "),
+    )
+}
+
+/// Names of the functions `ir` defines, with their `@`.
+fn defined_functions(ir: &str) -> HashSet<&str> {
+    ir.lines()
+        .filter(|line| line.starts_with("define "))
+        .filter_map(|line| references(line, b'@').next())
+        .collect()
+}
+
+/// What `ir` refers to by `sigil`, leaving out strings and comments: the names of
+/// globals and functions for `@`, and the numbers of attribute groups for `#` and
+/// of metadata for `!`, all with their sigil.
+fn references(ir: &str, sigil: u8) -> impl Iterator<Item = &str> {
+    let bytes = ir.as_bytes();
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        while i < bytes.len() {
+            let start = i;
+            i += 1;
+            match bytes[start] {
+                b'"' => i += memchr(b'"', &bytes[i..]).map_or(bytes.len() - i, |end| end + 1),
+                b';' => i += memchr(b'\n', &bytes[i..]).unwrap_or(bytes.len() - i),
+                byte if byte != sigil => {}
+                b'@' if bytes.get(i) == Some(&b'"') => {
+                    i += 1;
+                    i += memchr(b'"', &bytes[i..]).map_or(bytes.len() - i, |end| end + 1);
+                    return Some(&ir[start..i]);
+                }
+                _ => {
+                    while bytes.get(i).is_some_and(|&byte| match sigil {
+                        b'@' => {
+                            byte.is_ascii_alphanumeric()
+                                || matches!(byte, b'-' | b'$' | b'.' | b'_')
+                        }
+                        _ => byte.is_ascii_digit(),
+                    }) {
+                        i += 1;
+                    }
+                    // Named metadata and attachments like `!dbg` are no references
+                    let named = bytes.get(i).is_some_and(|byte| byte.is_ascii_alphabetic());
+                    if i > start + 1 && !named {
+                        return Some(&ir[start..i]);
+                    }
+                }
+            }
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optpipeline::{module_at, process, OptPipelineBackendOptions};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// `opt -passes='function(sroa,instcombine),cgscc(inline)'` of a module with a
+    /// global, a declaration and string attributes
+    const DUMP: &[u8] = include_bytes!("../tests/fixtures/print-before-after-globals.txt");
+
+    fn options() -> OptPipelineBackendOptions {
+        OptPipelineBackendOptions {
+            apply_filters: false,
+            ..Default::default()
+        }
+    }
+
+    /// Whether `opt` reads `reproducer` and runs its pass on it, unless `opt` isn't
+    /// installed.
+    fn opt_runs(reproducer: &Reproducer) -> Option<bool> {
+        let passes = format!("-passes={}", reproducer.opt_passes().unwrap());
+        let mut opt = Command::new("opt")
+            .args(["-disable-output", &passes])
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut stdin = opt.stdin.take().unwrap();
+        stdin.write_all(reproducer.ir.as_bytes()).unwrap();
+        drop(stdin);
+        Some(opt.wait().unwrap().success())
+    }
+
+    #[test]
+    fn reproducer_of_a_function_pass() {
+        let options = options();
+        let (_, result, _) = process(DUMP, &options).unwrap();
+        let pass = &result["sum"][2];
+        assert_eq!(pass.name, "InstCombinePass on sum");
+        let context = module_at(DUMP, &options, pass.line).unwrap();
+        let reproducer = reproducer(pass, &pass.before, context.as_deref()).unwrap();
+        assert!(reproducer.unresolved.is_empty());
+        assert_eq!(reproducer.opt_passes(), Some("instcombine"));
+        for line in [
+            "@g = global i32 0, align 4",
+            "declare void @ext(i32)",
+            "declare i32 @square(i32 %x) #0",
+            "attributes #1 = { nounwind \"frame-pointer\"=\"all\" }",
+        ] {
+            assert!(reproducer.ir.lines().any(|ir| ir == line), "{}", line);
+        }
+        assert_ne!(opt_runs(&reproducer), Some(false));
+
+        // Without the module, attributes come from the `; Function Attrs:` comment
+        let reproducer = super::reproducer(pass, &pass.before, None).unwrap();
+        assert_eq!(reproducer.unresolved, ["@ext", "@g", "@square"]);
+        assert!(reproducer.ir.contains("attributes #1 = { nounwind }"));
+    }
+
+    #[test]
+    fn reproducer_of_a_crash() {
+        let (_, result, _) = process(DUMP, &options()).unwrap();
+        let pipelines: Vec<&[Pass]> = result.values().map(Vec::as_slice).collect();
+        assert!(matches!(
+            crashed_ir(&pipelines),
+            Err(ReproduceError::NoCrash)
+        ));
+
+        let end = memchr::memmem::find(DUMP, b"*** IR Dump After InlinerPass on (sum)").unwrap();
+        let dump = &DUMP[..end];
+        let (_, result, _) = process(dump, &options()).unwrap();
+        let pipelines: Vec<&[Pass]> = result.values().map(Vec::as_slice).collect();
+        let (pass, ir) = crashed_ir(&pipelines).unwrap();
+        assert_eq!(pass.name, "InlinerPass on (sum)");
+        let context = module_at(dump, &options(), pass.line).unwrap();
+        let reproducer = reproducer(pass, &ir, context.as_deref()).unwrap();
+        assert!(reproducer.unresolved.is_empty());
+        assert_eq!(reproducer.opt_passes(), Some("inline"));
+        assert_ne!(opt_runs(&reproducer), Some(false));
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(
+            declaration("internal fastcc i32 @f(i32 %x, void (i32)* %g) unnamed_addr #3 align 2 {"),
+            Some("declare fastcc i32 @f(i32 %x, void (i32)* %g) unnamed_addr #3".to_string())
+        );
+        assert_eq!(
+            declaration("dso_local void @\"a b\"() #0 {"),
+            Some("declare dso_local void @\"a b\"() #0".to_string())
+        );
+    }
+
+    #[test]
+    fn references_leave_out_strings_and_comments() {
+        let ir = "call void @f(i8* @\"a b\", i8* @g.1) #2, !dbg !7, !range !12 ; @c !3\n@s = constant [2 x i8] c\"@x\"";
+        assert_eq!(
+            references(ir, b'@').collect_vec(),
+            ["@f", "@\"a b\"", "@g.1", "@s"]
+        );
+        assert_eq!(references(ir, b'#').collect_vec(), ["#2"]);
+        assert_eq!(references(ir, b'!').collect_vec(), ["!7", "!12"]);
+    }
+
+    #[test]
+    fn opt_pass_names() {
+        let reproducer = |pass| Reproducer {
+            pass,
+            ir: String::new(),
+            unresolved: Vec::new(),
+        };
+        let licm = reproducer("LICMPass on Loop at depth 1 containing: %loop<header>");
+        assert_eq!(licm.opt_passes(), Some("loop-mssa(licm)"));
+        assert_eq!(reproducer("MyPass on f").opt_passes(), None);
+    }
+}
@@ -0,0 +1,536 @@
+//! Interactive terminal UI for `--tui`, browsing functions, their passes and the
+//! diff of the selected pass.
+
+use crate::demangle::{demangle_text, DemangleMode};
+use crate::optpipeline::Pass;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use similar::TextDiff;
+use std::io;
+
+/// A function and the passes that survived the `--pass` filter.
+pub struct Function<'a> {
+    pub name: &'a str,
+    pub passes: Vec<(usize, &'a Pass)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Functions,
+    Passes,
+    Diff,
+}
+
+/// Key of the diff currently rendered: function, pass and how it was demangled.
+type DiffKey = (usize, usize, Option<DemangleMode>);
+
+struct App<'a> {
+    functions: Vec<Function<'a>>,
+    /// Names of `functions` as shown, demangled as `demangle` says
+    names: Vec<String>,
+    /// Lowercase `names` for the function search
+    search_names: Vec<String>,
+    skip_unchanged: bool,
+    demangle: Option<DemangleMode>,
+    /// The mode `d` turns demangling on with
+    demangle_mode: DemangleMode,
+    focus: Focus,
+    functions_state: ListState,
+    passes_state: ListState,
+    diff_scroll: u16,
+    diff_height: u16,
+    function_query: String,
+    pass_query: String,
+    searching: bool,
+    diff_cache: Option<(DiffKey, Vec<Line<'static>>)>,
+}
+
+pub fn run(
+    functions: Vec<Function<'_>>,
+    skip_unchanged: bool,
+    demangle: Option<DemangleMode>,
+) -> io::Result<()> {
+    let mut app = App::new(functions, skip_unchanged, demangle);
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl<'a> App<'a> {
+    fn new(
+        functions: Vec<Function<'a>>,
+        skip_unchanged: bool,
+        demangle: Option<DemangleMode>,
+    ) -> Self {
+        let mut app = App {
+            functions,
+            names: Vec::new(),
+            search_names: Vec::new(),
+            skip_unchanged,
+            demangle,
+            demangle_mode: demangle.unwrap_or(DemangleMode::Full),
+            focus: Focus::Functions,
+            functions_state: ListState::default(),
+            passes_state: ListState::default(),
+            diff_scroll: 0,
+            diff_height: 0,
+            function_query: String::new(),
+            pass_query: String::new(),
+            searching: false,
+            diff_cache: None,
+        };
+        app.functions_state.select(Some(0));
+        app.passes_state.select(Some(0));
+        app.update_names();
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Demangles the function names again after `demangle` changed.
+    fn update_names(&mut self) {
+        self.names = self
+            .functions
+            .iter()
+            .map(|func| demangle_text(func.name, self.demangle))
+            .collect();
+        self.search_names = self.names.iter().map(|name| name.to_lowercase()).collect();
+    }
+
+    /// Indices into `functions` matching the function search.
+    fn visible_functions(&self) -> Vec<usize> {
+        let query = self.function_query.to_lowercase();
+        (0..self.functions.len())
+            .filter(|&f| self.search_names[f].contains(&query))
+            .collect()
+    }
+
+    /// Indices into the passes of function `f` that are shown.
+    fn visible_passes(&self, f: usize) -> Vec<usize> {
+        let query = self.pass_query.to_lowercase();
+        self.functions[f]
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, pass))| !self.skip_unchanged || pass.ir_changed)
+            .filter(|(_, (_, pass))| pass.name.to_lowercase().contains(&query))
+            .map(|(p, _)| p)
+            .collect()
+    }
+
+    fn selected_function(&self) -> Option<usize> {
+        let visible = self.visible_functions();
+        visible.get(self.functions_state.selected()?).copied()
+    }
+
+    fn selected_pass(&self) -> Option<(usize, usize)> {
+        let f = self.selected_function()?;
+        let visible = self.visible_passes(f);
+        Some((f, *visible.get(self.passes_state.selected()?)?))
+    }
+
+    fn select_function(&mut self, idx: usize) {
+        if self.functions_state.selected() != Some(idx) {
+            self.functions_state.select(Some(idx));
+            self.passes_state.select(Some(0));
+            self.diff_scroll = 0;
+        }
+    }
+
+    fn select_pass(&mut self, idx: usize) {
+        if self.passes_state.selected() != Some(idx) {
+            self.passes_state.select(Some(idx));
+            self.diff_scroll = 0;
+        }
+    }
+
+    /// Select pass `p` of function `f`, making both visible.
+    fn jump_to(&mut self, f: usize, p: usize) {
+        if let Some(idx) = self.visible_functions().iter().position(|&v| v == f) {
+            self.select_function(idx);
+        }
+        if let Some(idx) = self.visible_passes(f).iter().position(|&v| v == p) {
+            self.select_pass(idx);
+        }
+    }
+
+    /// Move to the next (or previous) changed pass, continuing into other functions.
+    fn jump_to_changed(&mut self, forward: bool) {
+        let functions = self.visible_functions();
+        let Some(current_f) = self.functions_state.selected() else {
+            return;
+        };
+        // The search may hide all functions
+        if functions.is_empty() {
+            return;
+        }
+        let current_p = self.selected_pass().map(|(_, p)| p);
+
+        let order: Vec<usize> = if forward {
+            (current_f..functions.len()).collect()
+        } else {
+            (0..=current_f.min(functions.len().saturating_sub(1)))
+                .rev()
+                .collect()
+        };
+        for idx in order {
+            let f = functions[idx];
+            let passes = &self.functions[f].passes;
+            let candidates = self.visible_passes(f).into_iter().filter(|&p| {
+                passes[p].1.ir_changed
+                    && (idx != current_f
+                        || current_p.is_none_or(|cur| if forward { p > cur } else { p < cur }))
+            });
+            let target = if forward {
+                candidates.min()
+            } else {
+                candidates.max()
+            };
+            if let Some(p) = target {
+                self.jump_to(f, p);
+                return;
+            }
+        }
+    }
+
+    fn toggle_skip_unchanged(&mut self) {
+        let selected = self.selected_pass();
+        self.skip_unchanged = !self.skip_unchanged;
+        self.passes_state.select(Some(0));
+        if let Some((f, p)) = selected {
+            // Keep the selection on the same pass, or the closest one after it
+            let visible = self.visible_passes(f);
+            let idx = visible.iter().position(|&v| v >= p).unwrap_or(0);
+            self.passes_state.select(Some(idx));
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (len, selected) = match self.focus {
+            Focus::Functions => (
+                self.visible_functions().len(),
+                self.functions_state.selected(),
+            ),
+            Focus::Passes => match self.selected_function() {
+                Some(f) => (self.visible_passes(f).len(), self.passes_state.selected()),
+                None => return,
+            },
+            Focus::Diff => {
+                self.scroll_diff(delta);
+                return;
+            }
+        };
+        if len == 0 {
+            return;
+        }
+        let idx = selected
+            .unwrap_or(0)
+            .saturating_add_signed(delta)
+            .min(len - 1);
+        match self.focus {
+            Focus::Functions => self.select_function(idx),
+            _ => self.select_pass(idx),
+        }
+    }
+
+    fn scroll_diff(&mut self, delta: isize) {
+        let lines = self.diff_cache.as_ref().map_or(0, |(_, lines)| lines.len());
+        let max = lines.saturating_sub(1).min(u16::MAX as usize) as u16;
+        self.diff_scroll = (self.diff_scroll as isize + delta).clamp(0, max as isize) as u16;
+    }
+
+    /// Returns false when the app should quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.searching {
+            let query = match self.focus {
+                Focus::Functions => &mut self.function_query,
+                _ => &mut self.pass_query,
+            };
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    query.clear();
+                    self.searching = false;
+                }
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Char(c) => query.push(c),
+                _ => {}
+            }
+            match self.focus {
+                Focus::Functions => self.functions_state.select(Some(0)),
+                _ => self.passes_state.select(Some(0)),
+            }
+            return true;
+        }
+
+        let page = self.diff_height.max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Esc if self.function_query.is_empty() && self.pass_query.is_empty() => {
+                return false
+            }
+            KeyCode::Esc => {
+                self.function_query.clear();
+                self.pass_query.clear();
+                self.functions_state.select(Some(0));
+                self.passes_state.select(Some(0));
+            }
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter => {
+                self.focus = match self.focus {
+                    Focus::Functions => Focus::Passes,
+                    _ => Focus::Diff,
+                }
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.focus = match self.focus {
+                    Focus::Diff => Focus::Passes,
+                    _ => Focus::Functions,
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_diff(page),
+            KeyCode::PageUp | KeyCode::Char('b') => self.scroll_diff(-page),
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.scroll_diff(page / 2)
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.scroll_diff(-page / 2)
+            }
+            KeyCode::Char('n') => self.jump_to_changed(true),
+            KeyCode::Char('N') | KeyCode::Char('p') => self.jump_to_changed(false),
+            KeyCode::Char('s') => self.toggle_skip_unchanged(),
+            KeyCode::Char('d') => {
+                self.demangle = match self.demangle {
+                    Some(_) => None,
+                    None => Some(self.demangle_mode),
+                };
+                self.update_names();
+            }
+            KeyCode::Char('/') => {
+                if self.focus == Focus::Diff {
+                    self.focus = Focus::Passes;
+                }
+                self.searching = true;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn diff_lines(&mut self, f: usize, p: usize) -> &[Line<'static>] {
+        let key = (f, p, self.demangle);
+        if self
+            .diff_cache
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != key)
+        {
+            let (i, pass) = self.functions[f].passes[p];
+            let title = format!(
+                "({}·{}) {}{}",
+                i + 1,
+                self.names[f],
+                pass.name,
+                if pass.crashed { " (crashed)" } else { "" }
+            );
+            let mut lines = vec![Line::from(title).bold()];
+
+            if pass.crashed {
+                lines.push(
+                    Line::from("The dump ends in this pass, the compiler probably crashed in it. The IR it was given:")
+                        .fg(Color::Red),
+                );
+                lines.push(Line::default());
+                let before = demangle_text(&pass.before, self.demangle);
+                lines.extend(before.lines().map(|line| Line::from(line.to_string())));
+            } else if pass.ir_changed {
+                let before = demangle_text(&pass.before, self.demangle) + "\n";
+                let after = demangle_text(&pass.after, self.demangle) + "\n";
+                let diff = TextDiff::from_lines(&before, &after);
+                let unified = diff.unified_diff().context_radius(10).to_string();
+                lines.extend(unified.lines().map(|line| {
+                    let style = match line.as_bytes().first() {
+                        Some(b'+') => Style::new().fg(Color::Green),
+                        Some(b'-') => Style::new().fg(Color::Red),
+                        Some(b'@') => Style::new().fg(Color::Cyan),
+                        _ => Style::new(),
+                    };
+                    Line::styled(line.to_string(), style)
+                }));
+            } else {
+                lines.push(Line::from("No changes to the IR").italic());
+                lines.push(Line::default());
+                let after = demangle_text(&pass.after, self.demangle);
+                lines.extend(
+                    after
+                        .lines()
+                        .map(|line| Line::styled(format!(" {}", line), Style::new().dim())),
+                );
+            }
+            self.diff_cache = Some((key, lines));
+        }
+        &self.diff_cache.as_ref().unwrap().1
+    }
+
+    fn block(&self, title: String, focus: Focus) -> Block<'static> {
+        let block = Block::bordered().title(title);
+        if self.focus == focus {
+            block.border_style(Style::new().fg(Color::Yellow))
+        } else {
+            block
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [sidebar, diff_area] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(main);
+        let [functions_area, passes_area] =
+            Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(sidebar);
+
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let functions: Vec<ListItem> = self
+            .visible_functions()
+            .into_iter()
+            .map(|f| ListItem::new(self.names[f].clone()))
+            .collect();
+        let title = match self.function_query.as_str() {
+            "" => format!("Functions ({})", functions.len()),
+            query => format!("Functions ({}) /{}", functions.len(), query),
+        };
+        let list = List::new(functions)
+            .block(self.block(title, Focus::Functions))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, functions_area, &mut self.functions_state);
+
+        let passes: Vec<ListItem> = match self.selected_function() {
+            Some(f) => self
+                .visible_passes(f)
+                .into_iter()
+                .map(|p| {
+                    let (i, pass) = self.functions[f].passes[p];
+                    let item = ListItem::new(format!("{:>4} {}", i + 1, pass.name));
+                    if pass.crashed {
+                        item.style(Style::new().fg(Color::Red))
+                    } else if pass.ir_changed {
+                        item
+                    } else {
+                        item.style(Style::new().fg(Color::DarkGray))
+                    }
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let title = match self.pass_query.as_str() {
+            "" => format!("Passes ({})", passes.len()),
+            query => format!("Passes ({}) /{}", passes.len(), query),
+        };
+        let list = List::new(passes)
+            .block(self.block(title, Focus::Passes))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, passes_area, &mut self.passes_state);
+
+        self.diff_height = diff_area.height.saturating_sub(2);
+        let block = self.block("Diff".to_string(), Focus::Diff);
+        let diff = match self.selected_pass() {
+            Some((f, p)) => Paragraph::new(self.diff_lines(f, p).to_vec()),
+            None => Paragraph::new("No pass selected"),
+        };
+        frame.render_widget(diff.block(block).scroll((self.diff_scroll, 0)), diff_area);
+
+        let footer_line = if self.searching {
+            Line::from(vec![
+                Span::raw("Search: ").bold(),
+                Span::raw(match self.focus {
+                    Focus::Functions => self.function_query.as_str(),
+                    _ => self.pass_query.as_str(),
+                }),
+                Span::raw("█"),
+            ])
+        } else {
+            let toggle = |on| if on { "on" } else { "off" };
+            Line::from(format!(
+                " q quit  tab/←/→ focus  j/k move  n/N next/prev changed  space/b page  / search  s skip-unchanged: {}  d demangle: {}",
+                toggle(self.skip_unchanged),
+                toggle(self.demangle.is_some())
+            ))
+            .style(Style::new().fg(Color::DarkGray))
+        };
+        frame.render_widget(footer_line, footer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn pass(name: &str, before: &str, after: &str) -> Pass {
+        Pass {
+            name: name.to_string(),
+            machine: false,
+            before: Arc::from(before),
+            after: Arc::from(after),
+            ir_changed: before != after,
+            crashed: false,
+            line: 1,
+        }
+    }
+
+    #[test]
+    fn jump_to_changed_without_visible_functions() {
+        let passes = [pass("SROAPass on foo", "a", "b")];
+        let functions = vec![Function {
+            name: "foo",
+            passes: vec![(0, &passes[0])],
+        }];
+        let mut app = App::new(functions, false, None);
+        app.function_query = "bar".to_string();
+        app.jump_to_changed(true);
+        app.jump_to_changed(false);
+        assert_eq!(app.selected_pass(), None);
+
+        app.function_query.clear();
+        app.jump_to_changed(true);
+        assert_eq!(app.selected_pass(), Some((0, 0)));
+    }
+
+    #[test]
+    fn function_search_follows_demangling() {
+        let passes = [pass("SROAPass on _Z3bari", "a", "b")];
+        let functions = vec![Function {
+            name: "_Z3bari",
+            passes: vec![(0, &passes[0])],
+        }];
+        let mut app = App::new(functions, false, None);
+        app.function_query = "Bar(INT".to_string();
+        assert!(app.visible_functions().is_empty());
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE));
+        assert_eq!(app.visible_functions(), [0]);
+        assert_eq!(app.names, ["bar(int)"]);
+    }
+}
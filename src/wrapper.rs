@@ -0,0 +1,260 @@
+//! Running the compiler directly, as in `optdiff -- clang foo.c -O2`, with the
+//! flags needed for a pass dump injected.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use thiserror::Error;
+
+#[cfg(windows)]
+const NULL_DEVICE: &str = "NUL";
+#[cfg(not(windows))]
+const NULL_DEVICE: &str = "/dev/null";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Clang,
+    Opt,
+    Rustc,
+}
+
+#[derive(Debug, Error)]
+pub enum WrapperError {
+    #[error("Don't know how to get a pass dump from '{0}', expected clang, opt or rustc")]
+    UnsupportedCompiler(String),
+    #[error("Failed to run '{program}'")]
+    Spawn {
+        program: String,
+        source: std::io::Error,
+    },
+    #[error("'{program}' failed ({status}) without producing a pass dump:\n{stderr}")]
+    Failed {
+        program: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+}
+
+/// Options controlling which flags get injected.
+pub struct DumpFlags<'a> {
+    /// Functions to restrict printing to with `-filter-print-funcs`
+    pub functions: &'a [String],
+    /// Add `-print-module-scope`
+    pub full_module: bool,
+}
+
+impl Tool {
+    /// Guess the tool from the program name, e.g. `clang++-18` or `/usr/bin/opt`.
+    pub fn detect(program: &str) -> Option<Self> {
+        let name = Path::new(program).file_stem().and_then(OsStr::to_str)?;
+        if name.starts_with("clang") {
+            Some(Tool::Clang)
+        } else if name == "opt" || name.starts_with("opt-") {
+            Some(Tool::Opt)
+        } else if name == "rustc" || name.starts_with("rustc-") {
+            Some(Tool::Rustc)
+        } else {
+            None
+        }
+    }
+
+    fn llvm_flags(flags: &DumpFlags) -> Vec<String> {
+        let mut llvm_flags = vec![
+            "-print-before-all".to_string(),
+            "-print-after-all".to_string(),
+        ];
+        if !flags.functions.is_empty() {
+            llvm_flags.push(format!("-filter-print-funcs={}", flags.functions.join(",")));
+        }
+        if flags.full_module {
+            llvm_flags.push("-print-module-scope".to_string());
+        }
+        llvm_flags
+    }
+
+    /// Arguments to append to `args` so that the tool prints a pass dump to stderr
+    /// and doesn't write any output file the user didn't ask for.
+    fn injected_args(self, args: &[String], flags: &DumpFlags) -> Vec<String> {
+        let has = |flag: &str| {
+            args.iter()
+                .any(|arg| arg == flag || arg.starts_with(&format!("{}=", flag)))
+        };
+        // clang and rustc also take the output file attached, as in `-ofoo.o`
+        let has_output = || {
+            has("-o")
+                || args
+                    .iter()
+                    .any(|arg| arg.starts_with("-o") && !arg.starts_with("-obj"))
+        };
+        let mut injected = Vec::new();
+        match self {
+            Tool::Clang => {
+                for flag in Self::llvm_flags(flags) {
+                    injected.extend(["-mllvm".to_string(), flag]);
+                }
+                if !["-c", "-S", "-E", "-fsyntax-only"]
+                    .iter()
+                    .any(|flag| has(flag))
+                {
+                    injected.push("-c".to_string());
+                }
+                if !has_output() {
+                    injected.extend(["-o".to_string(), NULL_DEVICE.to_string()]);
+                }
+            }
+            Tool::Opt => {
+                injected.extend(Self::llvm_flags(flags));
+                // opt rejects `-ofoo`, and has other options starting with `-o`
+                if !has("-o") && !has("-disable-output") {
+                    injected.push("-disable-output".to_string());
+                }
+            }
+            Tool::Rustc => {
+                for flag in Self::llvm_flags(flags) {
+                    injected.extend(["-C".to_string(), format!("llvm-args={}", flag)]);
+                }
+                // Codegen units dump their passes in parallel, interleaving them
+                if !args.iter().any(|arg| arg.contains("codegen-units")) {
+                    injected.extend(["-C".to_string(), "codegen-units=1".to_string()]);
+                }
+                if !has("--emit") && !has_output() && !has("--out-dir") {
+                    injected.extend([
+                        "--emit=obj".to_string(),
+                        "-o".to_string(),
+                        NULL_DEVICE.to_string(),
+                    ]);
+                }
+            }
+        }
+        injected
+    }
+}
+
+pub struct CompilerOutput {
+    /// Everything the compiler printed to stderr
+    pub dump: String,
+    pub stdout: String,
+    pub status: ExitStatus,
+}
+
+/// Run `command` with pass dump flags injected and capture its stderr. A failing
+/// compiler is only an error if it didn't dump anything, so that the pipeline up
+/// to a crash can still be inspected.
+pub fn run(command: &[String], flags: &DumpFlags) -> Result<CompilerOutput, WrapperError> {
+    let (program, args) = command
+        .split_first()
+        .expect("clap guarantees a non-empty command");
+    let tool =
+        Tool::detect(program).ok_or_else(|| WrapperError::UnsupportedCompiler(program.clone()))?;
+
+    let output = Command::new(program)
+        .args(args)
+        .args(tool.injected_args(args, flags))
+        .output()
+        .map_err(|source| WrapperError::Spawn {
+            program: program.clone(),
+            source,
+        })?;
+
+    let dump = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() && !dump.contains("IR Dump") {
+        return Err(WrapperError::Failed {
+            program: program.clone(),
+            status: output.status,
+            stderr: dump,
+        });
+    }
+
+    Ok(CompilerOutput {
+        dump,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        status: output.status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injected(tool: Tool, args: &[&str]) -> Vec<String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let flags = DumpFlags {
+            functions: &["foo".to_string()],
+            full_module: false,
+        };
+        tool.injected_args(&args, &flags)
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(Tool::detect("/usr/bin/clang++-18"), Some(Tool::Clang));
+        assert_eq!(Tool::detect("opt-14"), Some(Tool::Opt));
+        assert_eq!(Tool::detect("rustc"), Some(Tool::Rustc));
+        assert_eq!(Tool::detect("gcc"), None);
+    }
+
+    #[test]
+    fn clang_args() {
+        assert_eq!(
+            injected(Tool::Clang, &["foo.c", "-O2"]),
+            [
+                "-mllvm",
+                "-print-before-all",
+                "-mllvm",
+                "-print-after-all",
+                "-mllvm",
+                "-filter-print-funcs=foo",
+                "-c",
+                "-o",
+                NULL_DEVICE,
+            ]
+        );
+        for output in [&["-o", "foo.o"][..], &["-ofoo.o"], &["-o=foo.o"]] {
+            let args = [&["foo.c", "-S"][..], output].concat();
+            assert!(!injected(Tool::Clang, &args).contains(&"-o".to_string()));
+        }
+        let args = ["foo.m", "-c", "-objcmt-migrate-literals"];
+        assert!(injected(Tool::Clang, &args).contains(&"-o".to_string()));
+    }
+
+    #[test]
+    fn opt_args() {
+        assert_eq!(
+            injected(Tool::Opt, &["-O2", "foo.ll"]),
+            [
+                "-print-before-all",
+                "-print-after-all",
+                "-filter-print-funcs=foo",
+                "-disable-output",
+            ]
+        );
+        let args = ["-O2", "foo.ll", "-o", "foo.bc"];
+        assert!(!injected(Tool::Opt, &args).contains(&"-disable-output".to_string()));
+    }
+
+    #[test]
+    fn rustc_args() {
+        assert_eq!(
+            injected(Tool::Rustc, &["-O", "foo.rs"]),
+            [
+                "-C",
+                "llvm-args=-print-before-all",
+                "-C",
+                "llvm-args=-print-after-all",
+                "-C",
+                "llvm-args=-filter-print-funcs=foo",
+                "-C",
+                "codegen-units=1",
+                "--emit=obj",
+                "-o",
+                NULL_DEVICE,
+            ]
+        );
+        let injected_args = injected(
+            Tool::Rustc,
+            &["-O", "foo.rs", "-Ccodegen-units=4", "-ofoo.o"],
+        );
+        assert!(!injected_args.contains(&"codegen-units=1".to_string()));
+        assert!(!injected_args.contains(&"-o".to_string()));
+    }
+}